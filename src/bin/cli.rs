@@ -1,25 +1,41 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::{Arg, Parser};
 
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use recorder::{
+    control_server::{parse_address, ControlServer},
     nodes::list_available_nodes,
     pipeline_processing::{
+        node::NodeID,
         parametrizable::prelude::*,
         processing_context::ProcessingContext,
-        processing_graph::{ProcessingGraphBuilder, ProcessingNodeConfig, SerdeNodeConfig},
+        processing_graph::{
+            GraphFormat,
+            ProcessingGraph,
+            ProcessingGraphBuilder,
+            ProcessingNodeConfig,
+            SerdeNodeConfig,
+        },
+        schema::{config_schema, node_catalogue},
+        subgraph::Subgraph,
     },
 };
 use serde::Deserialize;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
+    io::BufRead,
     iter::once,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 #[derive(Deserialize, Debug)]
 struct PipelineConfig {
+    /// sub-graph definitions that can be used as node types, relative to the
+    /// config file
+    #[serde(default)]
+    subgraphs: Vec<std::path::PathBuf>,
     #[serde(flatten)]
     nodes: HashMap<String, SerdeNodeConfig>,
 }
@@ -47,7 +63,14 @@ enum Command {
     #[clap(after_help = leak(&format!("NODES:\n{}", nodes_usages_string())).as_str())]
     FromCli {
         #[clap(
-            help = "example: <Node1> --source-arg ! <Node2> --sink-arg",
+            help = "example: <Node1> --source-arg ! <Node2> name=tee ! <Sink1> --sink-arg tee. ! <Sink2>",
+            long_help = "example: <Node1> --source-arg ! <Node2> name=tee ! <Sink1> --sink-arg tee. ! <Sink2>\n\n\
+                `name=<name>` directly after the node type names a node. \
+                `<name>.` starts a new branch from the named node, `<name>.<output>` from \
+                one of its named outputs. \
+                `! <name>.<input>` connects into the input `<input>` of the named node \
+                (`! <name>.` connects into its `input`, `! <name>.inputs[0]` into the first \
+                element of a list of inputs).",
             allow_hyphen_values(true)
         )]
         pipeline: Vec<String>,
@@ -66,6 +89,33 @@ enum Command {
         )]
         vars: Vec<String>,
     },
+    /// print the pipeline of a (yaml) configuration file as a graph without
+    /// running it
+    Graph {
+        /// path to the configuration file
+        file: std::path::PathBuf,
+        /// variables to substitute in the config file
+        #[clap(
+            short = 's',
+            long = "set",
+            name = "key=value",
+            allow_hyphen_values(true),
+            takes_value = true
+        )]
+        vars: Vec<String>,
+        /// output format
+        #[clap(long, default_value = "dot", possible_values = ["dot", "mermaid"])]
+        format: GraphFormat,
+    },
+    /// list the available nodes and their parameters
+    Nodes {
+        /// print the catalogue as json, including the ranges and defaults of
+        /// the parameters
+        #[clap(long)]
+        json: bool,
+    },
+    /// print a JSON Schema for pipeline configuration files
+    Schema,
 }
 
 /// Raw Image / Video Converter
@@ -82,57 +132,151 @@ struct Args {
     /// show a progress bar
     #[clap(long, short)]
     show_progress: bool,
+    /// print per node statistics every n seconds and once the pipeline finished
+    #[clap(long, value_name = "seconds")]
+    stats: Option<f64>,
+    /// read commands from stdin while the pipeline runs. `set <node>
+    /// <key>=<value>...` changes live parameters of a node, `stop` stops the
+    /// pipeline. Every command is answered with `ok` or `error: <message>`
+    #[clap(long)]
+    control_stdin: bool,
+    /// serve a HTTP/JSON api to control the running pipeline on `[host:]port`
+    #[clap(long, value_name = "address")]
+    control_http: Option<String>,
+    /// names of sinks that start disarmed, they can be started with the
+    /// control api
+    #[clap(long, value_name = "sink")]
+    disarmed: Vec<String>,
+    /// ignore node parameters that are not known instead of failing, for
+    /// configs written for newer versions
+    #[clap(long)]
+    allow_unknown_parameters: bool,
 }
 
 // used to have the convenience of ? for error handling
 fn work() -> Result<()> {
     let args = Args::parse();
+
+    // rendering the graph must not open any devices or files, so this happens
+    // before the processing context is created
+    match args.command {
+        Command::Graph { file, vars, format } => {
+            print!("{}", graph_builder_from_file(file, vars)?.render(format)?);
+            return Ok(());
+        }
+        Command::Nodes { json: true } => {
+            println!("{:#}", node_catalogue(&list_available_nodes()));
+            return Ok(());
+        }
+        Command::Nodes { json: false } => {
+            print!("{}", nodes_usages_string());
+            return Ok(());
+        }
+        Command::Schema => {
+            println!("{:#}", config_schema(&list_available_nodes()));
+            return Ok(());
+        }
+        _ => {}
+    }
+
     let processing_context = ProcessingContext::default();
+    processing_context.cancel_on_signals();
 
-    let processing_graph = match args.command {
+    let mut graph_builder = match args.command {
         Command::FromCli { pipeline } => {
-            let node_commandlines = pipeline.split(|element| element == "!").collect::<Vec<_>>();
-            let mut processing_graph = ProcessingGraphBuilder::new();
-
-            for (i, node_cmd) in node_commandlines.iter().enumerate() {
-                processing_graph.add(
-                    i,
-                    processing_node_from_commandline(
-                        node_cmd,
-                        if i > 0 { Some(i - 1) } else { None },
-                    )?,
-                )?;
+            let mut graph_builder = ProcessingGraphBuilder::new();
+
+            for (name, node) in processing_nodes_from_commandline(&pipeline)? {
+                graph_builder.add(name, node)?;
             }
 
-            processing_graph.build(&processing_context)?
+            graph_builder
         }
-        Command::FromFile { file, vars } => {
-            let vars = vars.into_iter().map(|v| {
-                let mut split = v.splitn(2, '=');
-                let name = split.next().unwrap();
-                let value = split.next().ok_or_else(|| anyhow::anyhow!("expected variable name value pair `{v}` to contain atleast one equals (=) sign"))?;
-                Ok((name.to_owned(), value.to_owned()))
-            }).collect::<Result<BTreeMap<_, _>>>()?;
-            let mut handlebars = handlebars::Handlebars::new();
-            handlebars.set_strict_mode(true);
-            let config: PipelineConfig = serde_yaml::from_str(
-                &handlebars.render_template(&std::fs::read_to_string(file)?, &vars)?,
-            )?;
-
-            let mut processing_graph = ProcessingGraphBuilder::new();
-
-            for (name, node) in config.nodes {
-                processing_graph.add(name, node.into())?;
-            }
-            processing_graph.build(&processing_context)?
+        Command::FromFile { file, vars } => graph_builder_from_file(file, vars)?,
+        Command::Graph { .. } | Command::Nodes { .. } | Command::Schema => unreachable!(),
+    };
+    graph_builder.allow_unknown_parameters(args.allow_unknown_parameters);
+    let node_ids = graph_builder.node_ids().clone();
+    let processing_graph = Arc::new(graph_builder.build(&processing_context)?);
+
+
+    for warning in processing_graph.warnings() {
+        eprintln!("warning: {warning}");
+    }
+
+    let stats_report = {
+        let graph = processing_graph.clone();
+        let context = processing_context.clone();
+        move || {
+            let metrics = graph.metrics();
+            let caches = metrics
+                .iter()
+                .filter_map(|(id, node)| {
+                    Some(format!("{}: {}\n", node.name, graph.cache_stats(id)?))
+                })
+                .collect::<String>();
+            let pool = context.buffer_pool();
+            format!(
+                "{}{caches}buffer pool: {} hits, {} misses, {:.1} MiB pooled",
+                metrics.report(),
+                pool.hits(),
+                pool.misses(),
+                pool.pooled_bytes() as f64 / (1024.0 * 1024.0)
+            )
         }
     };
+    if let Some(interval) = args.stats {
+        let stats_report = stats_report.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs_f64(interval));
+            eprintln!("\n{}", stats_report());
+        });
+    }
+
+    for name in &args.disarmed {
+        node_ids
+            .get(name)
+            .and_then(|id| processing_graph.sink_control(*id))
+            .ok_or_else(|| anyhow!("there is no sink named {name}"))?
+            .disarm()
+            .with_context(|| format!("couldn't disarm {name}"))?;
+    }
+
+    if let Some(address) = &args.control_http {
+        let address = parse_address(address)?;
+        let server = ControlServer::new(
+            processing_graph.clone(),
+            node_ids.clone(),
+            processing_context.clone(),
+        );
+        let context = processing_context.clone();
+        std::thread::spawn(move || {
+            if let Err(error) = context.block_on(server.serve(address)) {
+                eprintln!("control server failed: {error:?}");
+            }
+        });
+    }
 
+    if args.control_stdin {
+        let processing_graph = processing_graph.clone();
+        let context = processing_context.clone();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match run_control_command(&processing_graph, &node_ids, &context, &line) {
+                    Ok(()) => println!("ok"),
+                    Err(error) => println!("error: {error:#}"),
+                }
+            }
+        });
+    }
 
-    if args.show_progress {
+    let result = if args.show_progress {
         let progressbar: Arc<Mutex<Option<ProgressBar>>> = Default::default();
 
-        processing_graph.run(processing_context, move |progress| {
+        processing_graph.run(processing_context.clone(), move |progress| {
             let mut lock = progressbar.lock().unwrap();
             if lock.is_none() {
                 let progressbar = if let Some(total_frames) = progress.total_frames {
@@ -148,12 +292,88 @@ fn work() -> Result<()> {
                 *lock = Some(progressbar)
             }
             lock.as_ref().unwrap().set_position(progress.latest_frame);
-        })?;
+        })
     } else {
-        processing_graph.run(processing_context, |_| {})?;
+        processing_graph.run(processing_context.clone(), |_| {})
+    };
+
+    if args.stats.is_some() {
+        eprintln!("\n{}", stats_report());
+    }
+    if let Some(summary) = processing_context.errors().summary() {
+        eprint!("\n{summary}");
     }
 
-    Ok(())
+    result
+}
+
+fn run_control_command(
+    processing_graph: &ProcessingGraph,
+    node_ids: &HashMap<String, NodeID>,
+    context: &ProcessingContext,
+    line: &str,
+) -> Result<()> {
+    let words = shlex::split(line).ok_or_else(|| anyhow!("unbalanced quotes"))?;
+    match words.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["set", node, assignments @ ..] => {
+            let id = node_ids.get(*node).ok_or_else(|| anyhow!("there is no node named {node}"))?;
+            let values = assignments
+                .iter()
+                .map(|assignment| {
+                    assignment
+                        .split_once('=')
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .ok_or_else(|| anyhow!("expected <key>=<value>, got {assignment}"))
+                })
+                .collect::<Result<HashMap<_, _>>>()?;
+            if values.is_empty() {
+                bail!("set needs at least one <key>=<value>");
+            }
+            processing_graph.update_parameters(*id, &values)
+        }
+        ["stop"] => {
+            context.cancel();
+            Ok(())
+        }
+        _ => bail!("unknown command {line:?}, expected `set <node> <key>=<value>...` or `stop`"),
+    }
+}
+
+fn graph_builder_from_file(
+    file: std::path::PathBuf,
+    vars: Vec<String>,
+) -> Result<ProcessingGraphBuilder<String>> {
+    let vars = vars.into_iter().map(|v| {
+        let mut split = v.splitn(2, '=');
+        let name = split.next().unwrap();
+        let value = split.next().ok_or_else(|| anyhow::anyhow!("expected variable name value pair `{v}` to contain atleast one equals (=) sign"))?;
+        Ok((name.to_owned(), value.to_owned()))
+    }).collect::<Result<BTreeMap<_, _>>>()?;
+    let mut handlebars = handlebars::Handlebars::new();
+    handlebars.set_strict_mode(true);
+    let config: PipelineConfig = serde_yaml::from_str(
+        &handlebars.render_template(&std::fs::read_to_string(&file)?, &vars)?,
+    )?;
+
+    let directory = file.parent().unwrap_or_else(|| std::path::Path::new("."));
+    let subgraphs = config
+        .subgraphs
+        .iter()
+        .map(|path| Subgraph::load(&directory.join(path)).map(|s| (s.name.clone(), s)))
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let mut processing_graph = ProcessingGraphBuilder::new();
+
+    for (name, node) in config.nodes {
+        let node: ProcessingNodeConfig<String> = node.into();
+        match subgraphs.get(&node.name) {
+            Some(subgraph) => processing_graph.add_subgraph(name, subgraph, node, &vars)?,
+            None => {
+                processing_graph.add(name, node)?;
+            }
+        }
+    }
+    Ok(processing_graph)
 }
 
 fn nodes_usages_string() -> String {
@@ -173,10 +393,133 @@ fn nodes_usages_string() -> String {
         })
         .join("")
 }
+/// Parses the `from-cli` pipeline syntax into named node configs.
+///
+/// Nodes are chained with `!`. `name=<name>` directly after the node type
+/// names a node, `<name>.` in place of a node starts a new branch from a named
+/// node (`<name>.<output>` from one of its named outputs) and
+/// `! <name>.<input>` connects the previous node into the input `<input>` of a
+/// named node (`! <name>.` connects into `input`). Unnamed nodes get a name of
+/// the form `<position>:<type>`.
+fn processing_nodes_from_commandline(
+    pipeline: &[String],
+) -> Result<Vec<(String, ProcessingNodeConfig<String>)>> {
+    let available_nodes = list_available_nodes();
+    let declared_names: HashSet<&str> =
+        pipeline.iter().filter_map(|token| token.strip_prefix("name=")).collect();
+
+    let mut nodes: Vec<(String, ProcessingNodeConfig<String>)> = vec![];
+    // (from, to, input of to)
+    let mut links: Vec<(String, String, String)> = vec![];
+    let mut upstream: Option<String> = None;
+    let mut after_link = false;
+
+    let mut tokens = pipeline.iter().peekable();
+    while let Some(token) = tokens.next() {
+        if token == "!" {
+            if upstream.is_none() || after_link {
+                bail!("`!` needs to be preceded by a node or a reference to a node");
+            }
+            after_link = true;
+            continue;
+        }
+
+        if let Some((node, input)) = parse_node_reference(token, &declared_names) {
+            if after_link {
+                // connect into the named node, this ends the current chain
+                let from = upstream.take().unwrap();
+                links.push((from, node.to_string(), input.unwrap_or("input").to_string()));
+            } else {
+                // start a new branch from the named node, `<name>.<output>`
+                // refers to one of its named outputs
+                upstream = Some(match input {
+                    Some(output) => format!("{node}.{output}"),
+                    None => node.to_string(),
+                });
+            }
+            after_link = false;
+            continue;
+        }
+
+        if upstream.is_some() && !after_link {
+            bail!("expected `!` before {token}");
+        }
+
+        let descriptor = available_nodes.get(token).ok_or_else(|| {
+            anyhow!(
+                "cant find node with name {}. available nodes are: \n{}",
+                token,
+                nodes_usages_string()
+            )
+        })?;
+        let mut commandline = vec![token.clone()];
+        let mut name = None;
+        while let Some(&next) = tokens.peek() {
+            if next == "!" {
+                break;
+            }
+            let previous_takes_value = commandline
+                .last()
+                .and_then(|previous| previous.strip_prefix("--"))
+                .map_or(false, |key| option_takes_value(descriptor, key));
+            if !previous_takes_value && parse_node_reference(next, &declared_names).is_some() {
+                break;
+            }
+            match next.strip_prefix("name=") {
+                Some(declared) if commandline.len() == 1 && name.is_none() => {
+                    name = Some(declared.to_string())
+                }
+                _ => commandline.push(next.clone()),
+            }
+            tokens.next();
+        }
+
+        let name = name.unwrap_or_else(|| format!("{}:{}", nodes.len(), token));
+        if let Some(from) = upstream.replace(name.clone()) {
+            links.push((from, name.clone(), "input".to_string()));
+        }
+        nodes.push((name, processing_node_from_commandline(&commandline)?));
+        after_link = false;
+    }
+
+    if after_link {
+        bail!("the pipeline must not end with `!`");
+    }
+
+    for (from, to, input) in links {
+        let (_, node) = nodes
+            .iter_mut()
+            .find(|(name, _)| *name == to)
+            .ok_or_else(|| anyhow!("`{to}.` refers to a node that has no `name={to}`"))?;
+        if node.inputs.insert(input.clone(), from).is_some() {
+            bail!("input {input} of node {to} is connected more than once");
+        }
+    }
+
+    Ok(nodes)
+}
+
+/// Returns the node name and (optional) input name if `token` is a reference
+/// of the form `<name>.` or `<name>.<input>` to a declared node.
+fn parse_node_reference<'a>(
+    token: &'a str,
+    declared_names: &HashSet<&str>,
+) -> Option<(&'a str, Option<&'a str>)> {
+    let (node, input) = token.split_once('.')?;
+    declared_names.contains(node).then_some((node, Some(input).filter(|i| !i.is_empty())))
+}
+
+fn option_takes_value(descriptor: &ParameterizableDescriptor, key: &str) -> bool {
+    match descriptor.parameters_descriptor.0.get(key) {
+        Some(WithDefault(BoolParameter, BoolValue(false))) => false,
+        Some(_) => true,
+        None => false,
+    }
+}
+
 fn processing_node_from_commandline(
     commandline: &[String],
-    input: Option<usize>,
-) -> Result<ProcessingNodeConfig<usize>> {
+) -> Result<ProcessingNodeConfig<String>> {
     let name = commandline.get(0).ok_or(anyhow!("need to specify at least two nodes for a pipeline\nsee --help for instructions on how to use this tool"))?;
 
     let available_nodes: HashMap<String, ParameterizableDescriptor> = list_available_nodes();
@@ -198,12 +541,7 @@ fn processing_node_from_commandline(
     let parameters: HashMap<_, _> = parameters_description
         .0
         .iter()
-        .filter(|(_, descriptor)| {
-            !matches!(
-                descriptor,
-                Mandatory(NodeInputParameter) | WithDefault(NodeInputParameter, _)
-            )
-        })
+        .filter(|(_, descriptor)| !descriptor.get_parameter_type().is_node_input())
         .filter_map(|(key, parameter_type)| {
             results.value_of(key).map(|v| {
                 Ok((
@@ -217,11 +555,11 @@ fn processing_node_from_commandline(
         })
         .collect::<Result<_, anyhow::Error>>()?;
 
-    Ok(ProcessingNodeConfig::single_input_node(
-        name.to_string(),
-        Parameters::new(parameters),
-        input,
-    ))
+    Ok(ProcessingNodeConfig {
+        name: name.to_string(),
+        parameters: Parameters::new(parameters),
+        inputs: HashMap::new(),
+    })
 }
 
 fn leak<T: Clone>(s: &T) -> &'static T { Box::leak(Box::new(s.clone())) }
@@ -244,11 +582,11 @@ fn clap_app_from_node_name(name: &str) -> Result<clap::Command<'static>> {
     let parameters_description = leak(&node_descriptor.parameters_descriptor);
     for (key, parameter_type) in parameters_description.0.iter() {
         let parameter_type = leak(parameter_type);
-        if let Mandatory(NodeInputParameter) | WithDefault(NodeInputParameter, _) = parameter_type {
+        if parameter_type.get_parameter_type().is_node_input() {
             continue;
         };
         let parameter_type_for_closure = parameter_type.clone();
-        app = app.arg(match parameter_type {
+        let arg = match parameter_type {
             Mandatory(_) => Arg::new(leak(&key).as_str())
                 .long(key)
                 .takes_value(true)
@@ -289,7 +627,71 @@ fn clap_app_from_node_name(name: &str) -> Result<clap::Command<'static>> {
                         .map_err(|e| format!("{}", e))
                 })
                 .required(false),
+        };
+        app = app.arg(match parameter_type.get_parameter_type() {
+            Choice(choices) => arg.possible_values(choices.iter().copied()).ignore_case(true),
+            _ => arg,
         })
     }
     Ok(app)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::processing_nodes_from_commandline;
+
+    #[test]
+    fn test_branching_pipeline() {
+        let pipeline = "NullFrameSource --width 64 --height 64 --uint-bits 12 ! Cache name=t \
+                        ! RawBlobWriter --path t.raw t. ! BitDepthConverter ! BenchmarkSink";
+        let nodes = processing_nodes_from_commandline(
+            &pipeline.split_whitespace().map(str::to_string).collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let names = nodes.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["0:NullFrameSource", "t", "2:RawBlobWriter", "3:BitDepthConverter", "4:BenchmarkSink"]
+        );
+        let input_of = |i: usize| nodes[i].1.inputs.get("input").map(String::as_str);
+        assert_eq!(input_of(0), None);
+        assert_eq!(input_of(1), Some("0:NullFrameSource"));
+        assert_eq!(input_of(2), Some("t"));
+        assert_eq!(input_of(3), Some("t"));
+        assert_eq!(input_of(4), Some("3:BitDepthConverter"));
+    }
+
+    #[test]
+    fn test_branch_from_named_output() {
+        let pipeline = "NullFrameSource --width 64 --height 64 --uint-bits 12 ! Histogram name=h \
+                        ! BenchmarkSink h.image ! BenchmarkSink";
+        let nodes = processing_nodes_from_commandline(
+            &pipeline.split_whitespace().map(str::to_string).collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let input_of = |i: usize| nodes[i].1.inputs.get("input").map(String::as_str);
+        assert_eq!(input_of(2), Some("h"));
+        assert_eq!(input_of(3), Some("h.image"));
+    }
+
+    #[test]
+    fn test_choice_parameter() {
+        let parse = |pipeline: &str| {
+            processing_nodes_from_commandline(
+                &pipeline.split_whitespace().map(str::to_string).collect::<Vec<_>>(),
+            )
+        };
+        let nodes = parse(
+            "NullFrameSource --width 8 --height 8 --bayer gbgr --uint-bits 8 ! BenchmarkSink",
+        )
+        .unwrap();
+        let bayer = nodes[0].1.parameters.iter().find(|(key, _)| *key == "bayer").unwrap().1;
+        assert_eq!(bayer.to_string(), "GBGR");
+
+        let error = parse("NullFrameSource --width 8 --height 8 --bayer RGGB ! BenchmarkSink")
+            .err()
+            .unwrap();
+        assert!(format!("{error:#}").contains("RGGB"), "{error:#}");
+    }
+}
@@ -41,12 +41,22 @@ pub fn player(context: &mut WidgetContext) -> Fragment {
                     )?
                     .into(),
                 )?;
+                graph_builder.add(
+                    "prefetch".to_string(),
+                    serde_yaml::from_str::<SerdeNodeConfig>(
+                        "
+                    type: Prefetch
+                    input: <reader
+                ",
+                    )?
+                    .into(),
+                )?;
                 graph_builder.add(
                     "converter".to_string(),
                     serde_yaml::from_str::<SerdeNodeConfig>(
                         "
                     type: BitDepthConverter
-                    input: <reader
+                    input: <prefetch
                 ",
                     )?
                     .into(),
@@ -0,0 +1,262 @@
+use crate::{
+    nodes::list_available_nodes,
+    pipeline_processing::{
+        node::NodeID,
+        processing_context::ProcessingContext,
+        processing_graph::ProcessingGraph,
+        sink_control::SinkControl,
+    },
+};
+use anyhow::{anyhow, Result};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body,
+    Method,
+    Request,
+    Response,
+    Server,
+    StatusCode,
+};
+use serde_json::{json, Value};
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::Arc};
+
+#[derive(thiserror::Error, Debug)]
+enum ControlError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0:#}")]
+    BadRequest(anyhow::Error),
+}
+
+impl ControlError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ControlError::NotFound(_) => StatusCode::NOT_FOUND,
+            ControlError::BadRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// A HTTP server to remote control a running `ProcessingGraph`. Requests and
+/// responses are JSON:
+///
+/// * `GET /nodes`: the nodes with their current and live parameters
+/// * `PUT /nodes/<name>/parameters`: updates live parameters, the body is an
+///   object of parameter names and values
+/// * `GET /status`: the progress and fps of the sinks, the pulls and frame
+///   cache statistics of the nodes and the errors so far
+/// * `POST /sinks/<name>/start` and `POST /sinks/<name>/stop`: arm and disarm a
+///   sink, the other sinks keep running. Sinks that share a `Cache` with other
+///   consumers can't be disarmed.
+/// * `POST /shutdown`: stops the pipeline after the frames in flight
+///
+/// Errors are answered with a 4xx status and `{"error": <message>}`.
+pub struct ControlServer {
+    graph: Arc<ProcessingGraph>,
+    node_ids: HashMap<String, NodeID>,
+    context: ProcessingContext,
+}
+
+impl ControlServer {
+    pub fn new(
+        graph: Arc<ProcessingGraph>,
+        node_ids: HashMap<String, NodeID>,
+        context: ProcessingContext,
+    ) -> Self {
+        Self { graph, node_ids, context }
+    }
+
+    /// Serves requests until the pipeline is stopped. Has to be polled from
+    /// within a tokio runtime.
+    pub async fn serve(self, address: SocketAddr) -> Result<()> {
+        let shutdown_context = self.context.clone();
+        let server = Arc::new(self);
+        let service = make_service_fn(|_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&address)?.serve(service);
+        println!("control server listening on http://{}", server.local_addr());
+        server.with_graceful_shutdown(shutdown_context.cancelled()).await?;
+
+        Ok(())
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let (parts, body) = request.into_parts();
+        let path = parts.uri.path().trim_matches('/').split('/').collect::<Vec<_>>();
+        let result = match (&parts.method, path.as_slice()) {
+            (&Method::GET, ["nodes"]) => Ok(self.nodes()),
+            (&Method::PUT, ["nodes", name, "parameters"]) => {
+                match hyper::body::to_bytes(body).await {
+                    Ok(body) => self.update_parameters(name, &body),
+                    Err(error) => Err(ControlError::BadRequest(error.into())),
+                }
+            }
+            (&Method::GET, ["status"]) => Ok(self.status()),
+            (&Method::POST, ["sinks", name, "start"]) => {
+                self.sink(name).map(|sink| sink.arm()).map(|_| json!({}))
+            }
+            (&Method::POST, ["sinks", name, "stop"]) => self
+                .sink(name)
+                .and_then(|sink| sink.disarm().map_err(ControlError::BadRequest))
+                .map(|_| json!({})),
+            (&Method::POST, ["shutdown"]) => {
+                self.context.cancel();
+                Ok(json!({}))
+            }
+            (method, _) => {
+                Err(ControlError::NotFound(format!("no such endpoint: {method} {}", parts.uri)))
+            }
+        };
+
+        let (status, value) = match result {
+            Ok(value) => (StatusCode::OK, value),
+            Err(error) => (error.status(), json!({ "error": error.to_string() })),
+        };
+        Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(Body::from(value.to_string()))
+            .unwrap()
+    }
+
+    fn sorted_nodes(&self) -> Vec<(&String, NodeID)> {
+        let mut nodes = self.node_ids.iter().map(|(name, id)| (name, *id)).collect::<Vec<_>>();
+        nodes.sort_by_key(|(_, id)| usize::from(*id));
+        nodes
+    }
+
+    fn node_id(&self, name: &str) -> Result<NodeID, ControlError> {
+        self.node_ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| ControlError::NotFound(format!("there is no node named {name}")))
+    }
+
+    fn sink(&self, name: &str) -> Result<&SinkControl, ControlError> {
+        self.graph
+            .sink_control(self.node_id(name)?)
+            .map(|control| &**control)
+            .ok_or_else(|| ControlError::NotFound(format!("node {name} is not a sink")))
+    }
+
+    fn nodes(&self) -> Value {
+        let metrics = self.graph.metrics();
+        let available_nodes = list_available_nodes();
+        let nodes = self
+            .sorted_nodes()
+            .into_iter()
+            .map(|(name, id)| {
+                let node_type = metrics.get(id).map(|m| m.node_type.clone()).unwrap_or_default();
+                let mut live_parameters = available_nodes
+                    .get(&node_type)
+                    .map(|descriptor| {
+                        descriptor.parameters_descriptor.1.iter().cloned().collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                live_parameters.sort();
+                json!({
+                    "name": name,
+                    "type": node_type,
+                    "sink": self.graph.get_node(id).is_sink(),
+                    "parameters": self.graph.parameters(id),
+                    "live-parameters": live_parameters,
+                })
+            })
+            .collect::<Vec<_>>();
+        Value::Array(nodes)
+    }
+
+    fn update_parameters(&self, name: &str, body: &[u8]) -> Result<Value, ControlError> {
+        let id = self.node_id(name)?;
+        let values = serde_json::from_slice::<HashMap<String, Value>>(body)
+            .map_err(|error| ControlError::BadRequest(error.into()))?
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(value) => (key, value),
+                value => (key, value.to_string()),
+            })
+            .collect();
+        self.graph.update_parameters(id, &values).map_err(ControlError::BadRequest)?;
+        Ok(json!({ "parameters": self.graph.parameters(id) }))
+    }
+
+    fn status(&self) -> Value {
+        let metrics = self.graph.metrics();
+        let sinks = self
+            .sorted_nodes()
+            .into_iter()
+            .filter_map(|(name, id)| {
+                let control = self.graph.sink_control(id)?;
+                let progress = control.progress();
+                Some(json!({
+                    "name": name,
+                    "state": control.state().to_string(),
+                    "armed": control.is_armed(),
+                    "latest-frame": progress.latest_frame,
+                    "total-frames": progress.total_frames,
+                    "frames": progress.frames,
+                    "fps": progress.fps,
+                }))
+            })
+            .collect::<Vec<_>>();
+        let nodes = self
+            .sorted_nodes()
+            .into_iter()
+            .filter_map(|(name, id)| {
+                let metrics = metrics.get(id)?;
+                let cache = self.graph.cache_stats(id).map(|stats| {
+                    json!({
+                        "hits": stats.hits,
+                        "misses": stats.misses,
+                        "evictions": stats.evictions,
+                        "entries": stats.entries,
+                        "bytes": stats.bytes,
+                    })
+                });
+                Some(json!({
+                    "name": name,
+                    "pulls": metrics.pulls(),
+                    "errors": metrics.errors(),
+                    "cache": cache,
+                }))
+            })
+            .collect::<Vec<_>>();
+        let errors = self
+            .context
+            .errors()
+            .records()
+            .into_iter()
+            .map(|record| {
+                json!({
+                    "frame": record.frame,
+                    "error": record.error,
+                    "outcome": record.outcome.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "running": !self.context.is_cancelled(),
+            "sinks": sinks,
+            "nodes": nodes,
+            "errors": errors,
+        })
+    }
+}
+
+/// Parses the address of the control server, a bare port listens on
+/// localhost.
+pub fn parse_address(address: &str) -> Result<SocketAddr> {
+    if let Ok(port) = address.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    address.parse().map_err(|_| anyhow!("couldn't parse {address} as <host>:<port> or <port>"))
+}
@@ -1,5 +1,6 @@
 extern crate core;
 
+pub mod control_server;
 pub mod pipeline_processing;
 pub mod util;
 
@@ -8,5 +9,7 @@ mod nodes_cpu;
 mod nodes_gpu;
 mod nodes_io;
 mod nodes_util;
+#[cfg(test)]
+mod test_util;
 
 pub mod gui;
@@ -9,6 +9,7 @@ use crate::{
         //average::Average,
         benchmark_sink::BenchmarkSink,
         dual_frame_raw_decoder::{DualFrameRawDecoder, ReverseDualFrameRawDecoder},
+        fps_convert::FpsConvert,
         //sz3::SZ3Compress,
         zstd::ZstdBlobReader,
     },
@@ -26,9 +27,17 @@ use crate::{
         writer_cinema_dng::CinemaDngWriter,
         writer_raw::{RawBlobWriter, RawDirectoryWriter},
     },
-    nodes_util::{cache::Cache, split::Split},
+    nodes_util::{
+        cache::Cache,
+        concat::Concat,
+        lru_cache::LruCache,
+        prefetch::Prefetch,
+        range::Range,
+        split::Split,
+    },
     pipeline_processing::{
-        node::{Node, NodeID, ProcessingNodeIntoNode, SinkNodeIntoNode},
+        frame::FrameInterpretation,
+        node::{InputProcessingNode, Node, NodeID, ProcessingNodeIntoNode, SinkNodeIntoNode},
         parametrizable::prelude::*,
         processing_context::ProcessingContext,
     },
@@ -36,7 +45,7 @@ use crate::{
 use crate::{
     nodes_gpu::base_gpu_node::GpuNodeImpl,
     nodes_io::{frameserver_cinema_dng::CinemaDngFrameserver, writer_ffmpeg::FfmpegWriter},
-    nodes_util::null_source::NullFrameSource,
+    nodes_util::{null_source::NullFrameSource, test_pattern::TestPatternSource},
 };
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
@@ -52,18 +61,41 @@ macro_rules! generate_dynamic_node_creation_functions {
             to_return
         }
 
-        pub fn create_node_from_name(name: &str, node_id: NodeID, parameters: Parameters, inputs: HashMap<String, Node>, is_input_to: &[NodeID], context: &ProcessingContext) -> Result<Node> {
+        /// Creates a node by its type name. Parameters the node neither used nor
+        /// declared are an error, unless `allow_unknown_parameters` is set.
+        pub fn create_node_from_name(name: &str, parameters: Parameters, inputs: HashMap<String, InputProcessingNode>, is_input_to: &[NodeID], context: &ProcessingContext, allow_unknown_parameters: bool) -> Result<Node> {
             $(
                 $(#[$m])?
                 if name == <$x>::get_name() {
-                    let parameters = parameters.add_inputs(node_id, inputs)?;
-                    let parameters = parameters.add_defaults(<$x>::describe_parameters());
-                    return Ok(<$x>::from_parameters(parameters, is_input_to, &context)?.into_processing_element())
+                    let descriptor = <$x>::describe_parameters();
+                    let given = parameters.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+                    let used = parameters.used_keys();
+                    let parameters = parameters.add_inputs(inputs);
+                    let parameters = parameters.add_defaults(descriptor.clone())?;
+                    // checked even if creating the node failed, a typo is the
+                    // more helpful error than a missing parameter
+                    let node = <$x>::from_parameters(parameters, is_input_to, &context);
+                    if !allow_unknown_parameters {
+                        descriptor.check_unused(name, &given, &used.lock())?;
+                    }
+                    return Ok(node?.into_processing_element())
                 };
             )+
 
             Err(anyhow!("no node named {} found", name))
         }
+
+        /// See `Parameterizable::describe_output`, `None` for unknown node types.
+        pub fn describe_output_from_name(name: &str, parameters: Parameters, inputs: &HashMap<String, FrameInterpretation>) -> Option<FrameInterpretation> {
+            $(
+                $(#[$m])?
+                if name == <$x>::get_name() {
+                    return <$x>::describe_output(parameters.add_defaults(<$x>::describe_parameters()).ok()?, inputs)
+                };
+            )+
+
+            None
+        }
     };
 }
 
@@ -87,6 +119,11 @@ generate_dynamic_node_creation_functions![
     TcpReader,
     Cache,
     Split,
+    Range,
+    Concat,
+    FpsConvert,
+    Prefetch,
+    LruCache,
     //SZ3Compress,
     ZstdBlobReader,
     Calibrate,
@@ -98,4 +135,5 @@ generate_dynamic_node_creation_functions![
     FfmpegWriter,
     CinemaDngFrameserver,
     NullFrameSource,
+    TestPatternSource,
 ];
@@ -2,7 +2,7 @@ use crate::pipeline_processing::{
     node::{InputProcessingNode, NodeID, ProgressUpdate, SinkNode},
     parametrizable::{Parameterizable, Parameters, ParametersDescriptor},
     processing_context::ProcessingContext,
-    puller::{pull_ordered, pull_unordered},
+    puller::{pull_ordered, pull_unordered, ErrorPolicy, FrameCount},
 };
 use anyhow::Result;
 use async_trait::async_trait;
@@ -15,6 +15,7 @@ use crate::{pipeline_processing::parametrizable::prelude::*, util::fps_report::F
 pub struct BenchmarkSink {
     input: InputProcessingNode,
     priority: u8,
+    on_error: ErrorPolicy,
 }
 
 impl Parameterizable for BenchmarkSink {
@@ -22,6 +23,7 @@ impl Parameterizable for BenchmarkSink {
         ParametersDescriptor::new()
             .with("input", Mandatory(NodeInputParameter))
             .with("priority", WithDefault(U8(), ParameterValue::IntRangeValue(0)))
+            .with_error_policy()
     }
 
     fn from_parameters(
@@ -29,7 +31,11 @@ impl Parameterizable for BenchmarkSink {
         _is_input_to: &[NodeID],
         _context: &ProcessingContext,
     ) -> Result<Self> {
-        Ok(Self { input: parameters.take("input")?, priority: parameters.take("priority")? })
+        Ok(Self {
+            input: parameters.take("input")?,
+            priority: parameters.take("priority")?,
+            on_error: parameters.get_error_policy()?,
+        })
     }
 }
 
@@ -65,7 +71,8 @@ impl SinkNode for BenchmarkSink {
                 self.priority,
                 progress_callback.clone(),
                 self.input.clone_for_same_puller(),
-                None,
+                FrameCount::All,
+                self.on_error,
                 move |_input, _frame_number| Ok(()),
             )
             .await;
@@ -80,7 +87,8 @@ impl SinkNode for BenchmarkSink {
                     self.priority,
                     progress_callback.clone(),
                     self.input.clone_for_same_puller(),
-                    None,
+                    FrameCount::All,
+                    self.on_error,
                     move |_input, _frame_number| Ok(()),
                 )
                 .await?;
@@ -102,13 +110,17 @@ impl SinkNode for BenchmarkSink {
                 self.priority,
                 progress_callback,
                 self.input.clone_for_same_puller(),
-                None,
+                FrameCount::All,
+                self.on_error,
             );
             let reporter = FPSReporter::new("pipeline");
-            loop {
-                rx.recv_async().await?;
+            while rx.recv_async().await.is_ok() {
                 reporter.frame();
             }
+
+            Ok(())
         }
     }
+
+    fn check_input_types(&self) -> Result<()> { self.input.get_output_type().map(|_| ()) }
 }
@@ -1,18 +1,19 @@
 use crate::pipeline_processing::{
     node::InputProcessingNode,
     parametrizable::{Parameterizable, Parameters, ParametersDescriptor},
-    payload::Payload,
+    payload::{Payload, PayloadType},
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
 
 use crate::pipeline_processing::{
     frame::{Frame, FrameInterpretation, SampleInterpretation},
-    node::{Caps, NodeID, ProcessingNode, Request},
+    node::{Caps, DroppedError, NodeID, ProcessingNode, Request},
     parametrizable::prelude::*,
     processing_context::ProcessingContext,
 };
 use async_trait::async_trait;
+use std::collections::HashMap;
 
 pub struct BitDepthConverter {
     input: InputProcessingNode,
@@ -30,12 +31,33 @@ impl Parameterizable for BitDepthConverter {
     ) -> Result<Self> {
         Ok(Self { input: parameters.take("input")?, context: context.clone() })
     }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        output_interpretation(*inputs.get("input")?).ok()
+    }
+}
+
+fn output_interpretation(interpretation: FrameInterpretation) -> Result<FrameInterpretation> {
+    match interpretation.sample_interpretation {
+        SampleInterpretation::UInt(_) => Ok(FrameInterpretation {
+            sample_interpretation: SampleInterpretation::UInt(8),
+            ..interpretation
+        }),
+        other => bail!("BitDepthConverter can only convert uint frames, got {other:?}"),
+    }
 }
 
 #[async_trait]
 impl ProcessingNode for BitDepthConverter {
     async fn pull(&self, request: Request) -> Result<Payload> {
+        let droppable = request.is_droppable();
         let input = self.input.pull(request).await?;
+        if droppable {
+            return Err(DroppedError.into());
+        }
         let frame = self
             .context
             .ensure_cpu_buffer_frame(&input)
@@ -91,10 +113,47 @@ impl ProcessingNode for BitDepthConverter {
             });
         }
 
-        let new_frame = Frame { storage: new_buffer, interpretation };
+        let new_frame =
+            Frame { storage: new_buffer, interpretation, metadata: frame.metadata.clone() };
 
         Ok(Payload::from(new_frame))
     }
 
     fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input.get_output_type()?.map_frame(output_interpretation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitDepthConverter;
+    use crate::{
+        nodes_util::test_pattern::TestPatternSource,
+        pipeline_processing::{
+            frame::SampleInterpretation,
+            processing_context::ProcessingContext,
+            puller::FrameCount,
+        },
+        test_util::{assert_golden, collect_frames, input, node},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_12_bit_ramp() {
+        let context = ProcessingContext::default();
+        let yaml = "{type: TestPatternSource, pattern: ramp, width: 16, height: 4, uint-bits: 12}";
+        let source = node::<TestPatternSource>(&context, yaml, vec![]).unwrap();
+        let converter = node::<BitDepthConverter>(
+            &context,
+            "{type: BitDepthConverter}",
+            vec![("input", input(Arc::new(source)))],
+        )
+        .unwrap();
+
+        let frames = collect_frames(&context, Arc::new(converter), FrameCount::Indices(1)).unwrap();
+        assert_eq!(frames[0].interpretation.sample_interpretation, SampleInterpretation::UInt(8));
+        assert_golden("bit_depth_converter_ramp", &frames[0], 0.0);
+    }
 }
@@ -9,9 +9,9 @@ use crate::{
             FrameInterpretation,
             SampleInterpretation,
         },
-        node::{Caps, InputProcessingNode, NodeID, ProcessingNode, Request},
+        node::{Caps, Drop, DroppedError, InputProcessingNode, NodeID, ProcessingNode, Request},
         parametrizable::{prelude::*, Parameterizable, Parameters, ParametersDescriptor},
-        payload::Payload,
+        payload::{Payload, PayloadType},
         processing_context::ProcessingContext,
     },
     util::async_notifier::AsyncNotifier,
@@ -19,10 +19,20 @@ use crate::{
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use futures::join;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 const FRAME_A_MARKER: u8 = 0xAA;
 
+/// The counters the sensor embeds into the first bytes of each half frame,
+/// attached to the `FrameMetadata` of the frames produced by
+/// `DualFrameRawDecoder`.
+#[derive(Copy, Clone, Debug)]
+pub struct DualFrameCounters {
+    pub ctr_a: u8,
+    pub ctr_b: u8,
+    pub wrsel: u8,
+}
+
 #[derive(Clone, Default)]
 struct LastFrameInfo(u64, u64, u8, Option<Arc<Frame<CpuBuffer>>>);
 
@@ -38,7 +48,7 @@ impl Parameterizable for DualFrameRawDecoder {
         ParametersDescriptor::new()
             .with("input", Mandatory(NodeInputParameter))
             .with("debug", Optional(BoolParameter))
-            .with("bayer", WithDefault(StringParameter, StringValue("RGBG".to_string())))
+            .with("bayer", WithDefault(BayerPattern(), StringValue("RGBG".to_string())))
     }
 
     fn from_parameters(
@@ -46,13 +56,7 @@ impl Parameterizable for DualFrameRawDecoder {
         _is_input_to: &[NodeID],
         context: &ProcessingContext,
     ) -> Result<Self> {
-        let cfa_descriptor = match parameters.take::<String>("bayer")?.to_uppercase().as_str() {
-            "RGBG" => CfaDescriptor { red_in_first_col: true, red_in_first_row: true },
-            "BGRG" => CfaDescriptor { red_in_first_col: true, red_in_first_row: false },
-            "GBGR" => CfaDescriptor { red_in_first_col: false, red_in_first_row: true },
-            "GRGB" => CfaDescriptor { red_in_first_col: false, red_in_first_row: true },
-            _ => bail!("couldn't parse CFA Pattern"),
-        };
+        let cfa_descriptor = parse_cfa_descriptor(&mut parameters)?;
         Ok(Self {
             input: parameters.take("input")?,
             cfa_descriptor,
@@ -61,6 +65,40 @@ impl Parameterizable for DualFrameRawDecoder {
             context: context.clone(),
         })
     }
+
+    fn describe_output(
+        mut parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        let cfa_descriptor = parse_cfa_descriptor(&mut parameters).ok()?;
+        Some(decoded_interpretation(*inputs.get("input")?, cfa_descriptor))
+    }
+}
+
+fn parse_cfa_descriptor(parameters: &mut Parameters) -> Result<CfaDescriptor> {
+    Ok(match parameters.take::<String>("bayer")?.to_uppercase().as_str() {
+        "RGBG" => CfaDescriptor { red_in_first_col: true, red_in_first_row: true },
+        "BGRG" => CfaDescriptor { red_in_first_col: true, red_in_first_row: false },
+        "GBGR" => CfaDescriptor { red_in_first_col: false, red_in_first_row: true },
+        "GRGB" => CfaDescriptor { red_in_first_col: false, red_in_first_row: true },
+        _ => bail!("couldn't parse CFA Pattern"),
+    })
+}
+
+/// Two half frames with every second line become one frame with twice the
+/// size, at half the frame rate.
+fn decoded_interpretation(
+    interpretation: FrameInterpretation,
+    cfa_descriptor: CfaDescriptor,
+) -> FrameInterpretation {
+    FrameInterpretation {
+        width: interpretation.width * 2,
+        height: interpretation.height * 2,
+        fps: interpretation.fps.map(|v| v / 2.0),
+        color_interpretation: ColorInterpretation::Bayer(cfa_descriptor),
+        sample_interpretation: SampleInterpretation::UInt(12),
+        compression: Compression::Uncompressed,
+    }
 }
 
 #[async_trait]
@@ -70,6 +108,10 @@ impl ProcessingNode for DualFrameRawDecoder {
         let LastFrameInfo(_, next_even, last_wrsel, old_frame) =
             self.last_frame_info.wait(move |LastFrameInfo(next, ..)| *next == frame_number).await;
 
+        // the counters of the input frames are needed to stay in sync, so the inputs
+        // are pulled in full even if this frame gets dropped
+        let input_request = request.without_extra::<Drop>();
+
         let mut offset = 2;
         let pulled_frames_used_old = (|| async {
             match old_frame {
@@ -78,7 +120,7 @@ impl ProcessingNode for DualFrameRawDecoder {
                         .update(|LastFrameInfo(_, _, _, old_frame)| *old_frame = None);
 
                     offset = 1;
-                    let frame = self.input.pull(request.with_frame_number(next_even)).await?;
+                    let frame = self.input.pull(input_request.with_frame_number(next_even)).await?;
                     let frame_b = self
                         .context
                         .ensure_cpu_buffer_frame(&frame)
@@ -87,8 +129,8 @@ impl ProcessingNode for DualFrameRawDecoder {
                 }
                 None => {
                     let frames = join!(
-                        self.input.pull(request.with_frame_number(next_even)),
-                        self.input.pull(request.with_frame_number(next_even + 1))
+                        self.input.pull(input_request.with_frame_number(next_even)),
+                        self.input.pull(input_request.with_frame_number(next_even + 1))
                     );
                     let frame_a = self
                         .context
@@ -122,6 +164,13 @@ impl ProcessingNode for DualFrameRawDecoder {
         });
         let (frame_a, frame_b) = if swap { (frame_b, frame_a) } else { (frame_a, frame_b) };
 
+        let counters = frame_a.storage.as_slice(|frame_a| {
+            frame_b.storage.as_slice(|frame_b| DualFrameCounters {
+                ctr_a: frame_a[0],
+                ctr_b: frame_b[0],
+                wrsel: frame_a[1],
+            })
+        });
         let (is_correct, debug_info, wrsel) = frame_a.storage.as_slice(|frame_a| {
             frame_b.storage.as_slice(|frame_b| {
                 let debug_info = format!(
@@ -157,15 +206,11 @@ impl ProcessingNode for DualFrameRawDecoder {
         if !is_correct {
             return Err(anyhow!("frame slipped in DualFrameRawDecoder:\n{}", debug_info));
         }
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
 
-        let interpretation = FrameInterpretation {
-            width: frame_a.interpretation.width * 2,
-            height: frame_a.interpretation.height * 2,
-            fps: frame_a.interpretation.fps.map(|v| v / 2.0),
-            color_interpretation: ColorInterpretation::Bayer(self.cfa_descriptor),
-            sample_interpretation: SampleInterpretation::UInt(8),
-            compression: Compression::Uncompressed,
-        };
+        let interpretation = decoded_interpretation(frame_a.interpretation, self.cfa_descriptor);
 
         let mut new_buffer =
             unsafe { self.context.get_uninit_cpu_buffer(interpretation.required_bytes()) };
@@ -193,13 +238,22 @@ impl ProcessingNode for DualFrameRawDecoder {
             })
         });
 
-        Ok(Payload::from(Frame { interpretation, storage: new_buffer }))
+        let mut metadata = frame_a.metadata.clone();
+        metadata.insert(counters);
+
+        Ok(Payload::from(Frame { interpretation, storage: new_buffer, metadata }))
     }
 
     fn get_caps(&self) -> Caps {
         let upstream = self.input.get_caps();
         Caps { frame_count: upstream.frame_count.map(|x| x / 2), ..upstream }
     }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input.get_output_type()?.map_frame(|interpretation| {
+            Ok(decoded_interpretation(interpretation, self.cfa_descriptor))
+        })
+    }
 }
 
 
@@ -228,10 +282,37 @@ impl Parameterizable for ReverseDualFrameRawDecoder {
     ) -> Result<Self> {
         Ok(Self {
             input: parameters.take("input")?,
-            flip: parameters.take("flip")?,
+            flip: parameters.take_option("flip")?.unwrap_or(false),
             context: context.clone(),
         })
     }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        encoded_interpretation(*inputs.get("input")?).ok()
+    }
+}
+
+/// The half frames are sent as rgb frames of half the size, three bytes hold
+/// two 12 bit samples.
+fn encoded_interpretation(interpretation: FrameInterpretation) -> Result<FrameInterpretation> {
+    if !matches!(interpretation.sample_interpretation, SampleInterpretation::UInt(12)) {
+        bail!("A frame with bit_depth=12 is required. Convert the bit depth of the frame!")
+    }
+    if !matches!(interpretation.color_interpretation, ColorInterpretation::Bayer(_cfa)) {
+        bail!("A frame with bayer pattern is expected!")
+    }
+
+    Ok(FrameInterpretation {
+        width: interpretation.width / 2,
+        height: interpretation.height / 2,
+        fps: interpretation.fps.map(|v| v * 2.0),
+        color_interpretation: ColorInterpretation::Rgb,
+        sample_interpretation: SampleInterpretation::UInt(8),
+        compression: Compression::Uncompressed,
+    })
 }
 
 #[async_trait]
@@ -239,14 +320,11 @@ impl ProcessingNode for ReverseDualFrameRawDecoder {
     async fn pull(&self, request: Request) -> Result<Payload> {
         let downstream = request.frame_number() / 2;
         let frame = self.input.pull(request.with_frame_number(downstream)).await?;
-        let frame = self.context.ensure_cpu_buffer_frame(&frame)?;
-
-        if !matches!(frame.interpretation.sample_interpretation, SampleInterpretation::UInt(12)) {
-            bail!("A frame with bit_depth=12 is required. Convert the bit depth of the frame!")
-        }
-        if !matches!(frame.interpretation.color_interpretation, ColorInterpretation::Bayer(_cfa)) {
-            bail!("A frame with bayer pattern is expected!")
+        if request.is_droppable() {
+            return Err(DroppedError.into());
         }
+        let frame = self.context.ensure_cpu_buffer_frame(&frame)?;
+        let interpretation = encoded_interpretation(frame.interpretation)?;
 
         let offset = if self.flip { 1 } else { 0 };
         let offset = ((request.frame_number() + offset) % 2) as usize;
@@ -271,15 +349,9 @@ impl ProcessingNode for ReverseDualFrameRawDecoder {
         };
 
         Ok(Payload::from(Frame {
-            interpretation: FrameInterpretation {
-                width: frame.interpretation.width / 2,
-                height: frame.interpretation.height / 2,
-                fps: frame.interpretation.fps.map(|v| v * 2.0),
-                color_interpretation: ColorInterpretation::Rgb,
-                sample_interpretation: SampleInterpretation::UInt(8),
-                compression: Compression::Uncompressed,
-            },
+            interpretation,
             storage: out_buffer,
+            metadata: frame.metadata.clone(),
         }))
     }
 
@@ -287,4 +359,84 @@ impl ProcessingNode for ReverseDualFrameRawDecoder {
         let upstream = self.input.get_caps();
         Caps { frame_count: upstream.frame_count.map(|x| x * 2), ..upstream }
     }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input.get_output_type()?.map_frame(encoded_interpretation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DualFrameRawDecoder, ReverseDualFrameRawDecoder};
+    use crate::{
+        pipeline_processing::{
+            frame::{
+                CfaDescriptor,
+                ColorInterpretation,
+                Compression,
+                FrameInterpretation,
+                SampleInterpretation,
+            },
+            processing_context::ProcessingContext,
+            puller::FrameCount,
+        },
+        test_util::{collect_frames, frame, input, node, MockSource},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_round_trip() {
+        let context = ProcessingContext::default();
+        let interpretation = FrameInterpretation {
+            width: 8,
+            height: 4,
+            fps: None,
+            color_interpretation: ColorInterpretation::Bayer(CfaDescriptor {
+                red_in_first_col: true,
+                red_in_first_row: true,
+            }),
+            sample_interpretation: SampleInterpretation::UInt(12),
+            compression: Compression::Uncompressed,
+        };
+        // the first bytes of the two half frames carry the counters, the
+        // write select and the marker of the half frame with the odd lines
+        let line_bytes = 12;
+        let frames = [(1, 2, 5), (3, 4, 6)]
+            .into_iter()
+            .enumerate()
+            .map(|(n, (ctr_a, ctr_b, wrsel))| {
+                let mut bytes = (0..48).map(|i| ((n * 48 + i) * 7 % 251) as u8).collect::<Vec<_>>();
+                bytes[..3].copy_from_slice(&[ctr_a, wrsel, 0]);
+                bytes[line_bytes..line_bytes + 3].copy_from_slice(&[ctr_b, wrsel, 0xAA]);
+                bytes
+            })
+            .collect::<Vec<_>>();
+
+        let source = MockSource::new(
+            frames.iter().map(|bytes| frame(&context, interpretation, bytes)).collect(),
+        );
+        let reverse = node::<ReverseDualFrameRawDecoder>(
+            &context,
+            "{type: ReverseDualFrameRawDecoder}",
+            vec![("input", input(source.clone()))],
+        )
+        .unwrap();
+        let decoder = node::<DualFrameRawDecoder>(
+            &context,
+            "{type: DualFrameRawDecoder}",
+            vec![("input", input(Arc::new(reverse)))],
+        )
+        .unwrap();
+
+        let decoded = collect_frames(&context, Arc::new(decoder), FrameCount::All).unwrap();
+        assert_eq!(decoded.len(), 2);
+        for (decoded, original) in decoded.iter().zip(&frames) {
+            assert_eq!(decoded.interpretation, interpretation);
+            decoded.storage.as_slice(|decoded| assert_eq!(decoded, original));
+        }
+        let mut requests = source.requests();
+        requests.sort();
+        requests.dedup();
+        assert_eq!(requests, [0, 1]);
+    }
 }
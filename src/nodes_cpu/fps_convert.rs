@@ -0,0 +1,378 @@
+use crate::pipeline_processing::{
+    buffers::{CpuBuffer, GpuBuffer},
+    frame::{Compression, Frame, FrameInterpretation, SampleInterpretation},
+    node::{Caps, EOFError, InputProcessingNode, NodeID, ProcessingNode, Request},
+    parametrizable::prelude::*,
+    payload::{Payload, PayloadType},
+    processing_context::ProcessingContext,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    // the input frame closest in time, dropping or repeating frames
+    Nearest,
+    // a mix of the two input frames around the time of the output frame
+    Blend,
+    // every input frame is played at the new rate
+    Conform,
+}
+
+pub struct FpsConvert {
+    input: InputProcessingNode,
+    context: ProcessingContext,
+    mode: Mode,
+    fps: f64,
+    input_fps: f64,
+}
+
+impl Parameterizable for FpsConvert {
+    const DESCRIPTION: Option<&'static str> = Some(
+        "converts the frame rate by picking the nearest input frame, blending the adjacent ones \
+         or by only changing the fps (conform)",
+    );
+
+    fn describe_parameters() -> ParametersDescriptor {
+        ParametersDescriptor::new()
+            .with("input", Mandatory(NodeInputParameter))
+            .with("fps", Mandatory(PositiveReal()))
+            .with(
+                "mode",
+                WithDefault(
+                    Choice(&["nearest", "blend", "conform"]),
+                    StringValue("nearest".to_string()),
+                ),
+            )
+            // for inputs that only know their frame rate at runtime
+            .with("input-fps", Optional(PositiveReal()))
+    }
+
+    fn from_parameters(
+        mut parameters: Parameters,
+        _is_input_to: &[NodeID],
+        context: &ProcessingContext,
+    ) -> Result<Self> {
+        let input: InputProcessingNode = parameters.take("input")?;
+        let mode = Mode::from_parameters(&mut parameters)?;
+        let fps: f64 = parameters.take("fps")?;
+        let input_fps = match (parameters.take_option("input-fps")?, mode) {
+            (Some(input_fps), _) => input_fps,
+            // conform plays every input frame, the rate of the input doesn't matter
+            (None, Mode::Conform) => fps,
+            (None, _) => input
+                .get_output_type()?
+                .expect_frame()?
+                .and_then(|interpretation| interpretation.fps)
+                .context("the fps of the input are not known, set input-fps")?,
+        };
+        if fps <= 0.0 || input_fps <= 0.0 {
+            bail!("the frame rates have to be greater than zero");
+        }
+        if mode != Mode::Conform && !input.get_caps().random_access {
+            bail!("the input doesn't support random access, only the conform mode can be used");
+        }
+
+        Ok(Self { input, context: context.clone(), mode, fps, input_fps })
+    }
+
+    fn describe_output(
+        mut parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        let mode = Mode::from_parameters(&mut parameters).ok()?;
+        let fps = parameters.take("fps").ok()?;
+        mode.output_interpretation(*inputs.get("input")?, fps).ok()
+    }
+}
+
+impl Mode {
+    fn from_parameters(parameters: &mut Parameters) -> Result<Self> {
+        Ok(match parameters.take::<String>("mode")?.to_lowercase().as_str() {
+            "nearest" => Mode::Nearest,
+            "blend" => Mode::Blend,
+            "conform" => Mode::Conform,
+            other => bail!("unknown mode {other}"),
+        })
+    }
+
+    fn output_interpretation(
+        self,
+        interpretation: FrameInterpretation,
+        fps: f64,
+    ) -> Result<FrameInterpretation> {
+        if self == Mode::Blend {
+            if let SampleInterpretation::FP16 = interpretation.sample_interpretation {
+                bail!("FpsConvert can't blend fp16 frames");
+            }
+        }
+        Ok(FrameInterpretation { fps: Some(fps), ..interpretation })
+    }
+}
+
+impl FpsConvert {
+    /// The time of output frame `frame_number`, in input frames.
+    fn position(&self, frame_number: u64) -> f64 { frame_number as f64 * self.input_fps / self.fps }
+
+    /// The input frame closest to an output frame, ties go to the later one.
+    fn nearest(&self, frame_number: u64) -> u64 {
+        (self.position(frame_number) + 0.5).floor() as u64
+    }
+
+    /// The number of output frames for which all needed input frames exist.
+    fn frame_count(&self, input_frames: u64) -> u64 {
+        let ratio = self.input_fps / self.fps;
+        match self.mode {
+            Mode::Conform => input_frames,
+            // all frames whose nearest input frame is before the end
+            Mode::Nearest => ((input_frames as f64 - 0.5) / ratio).ceil() as u64,
+            // all frames up to the last input frame
+            Mode::Blend if input_frames == 0 => 0,
+            Mode::Blend => ((input_frames - 1) as f64 / ratio).floor() as u64 + 1,
+        }
+    }
+
+    fn with_fps(&self, interpretation: FrameInterpretation) -> FrameInterpretation {
+        FrameInterpretation { fps: Some(self.fps), ..interpretation }
+    }
+
+    async fn blend(&self, request: Request) -> Result<Payload> {
+        let position = self.position(request.frame_number());
+        let first = position.floor() as u64;
+        let weight = position - first as f64;
+        if weight < 1e-6 {
+            return self.relabel(self.input.pull(request.with_frame_number(first)).await?);
+        }
+
+        let (a, b) = futures::try_join!(
+            self.input.pull(request.with_frame_number(first)),
+            self.input.pull(request.with_frame_number(first + 1)),
+        )?;
+        let a = self
+            .context
+            .ensure_cpu_buffer_frame(&a)
+            .context("Wrong input format for FpsConvert")?;
+        let b = self
+            .context
+            .ensure_cpu_buffer_frame(&b)
+            .context("Wrong input format for FpsConvert")?;
+        if a.interpretation != b.interpretation {
+            bail!("can't blend frames {} and {} of different interpretations", first, first + 1);
+        }
+        if a.interpretation.compression != Compression::Uncompressed {
+            bail!("can't blend compressed frames");
+        }
+
+        let mut buffer =
+            unsafe { self.context.get_uninit_cpu_buffer(a.interpretation.required_bytes()) };
+        buffer.as_mut_slice(|out| {
+            a.storage.as_slice(|a_samples| {
+                b.storage.as_slice(|b_samples| {
+                    blend_samples(
+                        a_samples,
+                        b_samples,
+                        weight,
+                        a.interpretation.sample_interpretation,
+                        out,
+                    )
+                })
+            })
+        })?;
+
+        Ok(Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.with_fps(a.interpretation),
+            metadata: a.metadata.clone(),
+        }))
+    }
+
+    fn relabeled<S: Clone>(&self, frame: &Frame<S>) -> Frame<S> {
+        Frame {
+            storage: frame.storage.clone(),
+            interpretation: self.with_fps(frame.interpretation),
+            metadata: frame.metadata.clone(),
+        }
+    }
+
+    /// Only changes the fps of a frame, it stays in cpu or gpu memory.
+    fn relabel(&self, payload: Payload) -> Result<Payload> {
+        if let Some(frame) = payload.downcast_ref::<Frame<CpuBuffer>>() {
+            Ok(Payload::from(self.relabeled(frame)))
+        } else if let Some(frame) = payload.downcast_ref::<Frame<GpuBuffer>>() {
+            Ok(Payload::from(self.relabeled(frame)))
+        } else {
+            bail!("Wrong input format for FpsConvert: {}", payload.type_name)
+        }
+    }
+}
+
+/// Mixes two buffers of samples, `weight` is the share of `b`.
+fn blend_samples(
+    a: &[u8],
+    b: &[u8],
+    weight: f64,
+    interpretation: SampleInterpretation,
+    out: &mut [u8],
+) -> Result<()> {
+    let mix = |a: f64, b: f64| a + (b - a) * weight;
+    match interpretation {
+        SampleInterpretation::UInt(8) => {
+            for ((a, b), out) in a.iter().zip(b).zip(out) {
+                *out = mix(*a as f64, *b as f64).round() as u8;
+            }
+        }
+        SampleInterpretation::UInt(bits) => {
+            // samples are packed most significant bit first
+            let bits = bits as usize;
+            let read = |bytes: &[u8], i: usize| {
+                let (start, end) = (i * bits, (i * bits + bits + 7) / 8 * 8);
+                let window = (start / 8..end / 8)
+                    .fold(0u64, |window, byte| (window << 8) | bytes[byte] as u64);
+                (window >> (end - start - bits)) & ((1 << bits) - 1)
+            };
+            let (mut accumulator, mut accumulated_bits, mut pos) = (0u64, 0, 0);
+            for i in 0..a.len() * 8 / bits {
+                let sample = mix(read(a, i) as f64, read(b, i) as f64).round() as u64;
+                accumulator = (accumulator << bits) | sample;
+                accumulated_bits += bits;
+                while accumulated_bits >= 8 {
+                    accumulated_bits -= 8;
+                    out[pos] = (accumulator >> accumulated_bits) as u8;
+                    pos += 1;
+                }
+                accumulator &= (1 << accumulated_bits) - 1;
+            }
+            if accumulated_bits > 0 {
+                out[pos] = (accumulator << (8 - accumulated_bits)) as u8;
+            }
+        }
+        SampleInterpretation::FP32 => {
+            for ((a, b), out) in
+                a.chunks_exact(4).zip(b.chunks_exact(4)).zip(out.chunks_exact_mut(4))
+            {
+                let a = f32::from_ne_bytes([a[0], a[1], a[2], a[3]]);
+                let b = f32::from_ne_bytes([b[0], b[1], b[2], b[3]]);
+                out.copy_from_slice(&(mix(a as f64, b as f64) as f32).to_ne_bytes());
+            }
+        }
+        SampleInterpretation::FP16 => bail!("blending fp16 frames is not supported"),
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ProcessingNode for FpsConvert {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        if let Some(frame_count) = self.get_caps().frame_count {
+            if request.frame_number() >= frame_count {
+                return Err(EOFError.into());
+            }
+        }
+        match self.mode {
+            Mode::Conform => self.relabel(self.input.pull(request).await?),
+            Mode::Nearest => {
+                let input = request.with_frame_number(self.nearest(request.frame_number()));
+                self.relabel(self.input.pull(input).await?)
+            }
+            Mode::Blend => self.blend(request).await,
+        }
+    }
+
+    fn get_caps(&self) -> Caps {
+        let caps = self.input.get_caps();
+        Caps { frame_count: caps.frame_count.map(|count| self.frame_count(count)), ..caps }
+    }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input
+            .get_output_type()?
+            .map_frame(|interpretation| self.mode.output_interpretation(interpretation, self.fps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blend_samples, FpsConvert};
+    use crate::{
+        pipeline_processing::{
+            frame::{Frame, FrameInterpretation, SampleInterpretation},
+            node::ProcessingNode,
+            processing_context::ProcessingContext,
+            puller::FrameCount,
+        },
+        test_util::{
+            collect_frames,
+            counter_frames,
+            counter_value,
+            input,
+            node,
+            pack_samples,
+            MockSource,
+        },
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_fps_convert() {
+        let context = ProcessingContext::default();
+        let frames =
+            counter_frames(&context, (0..30).map(|n| n * 8)).into_iter().map(|frame| Frame {
+                interpretation: FrameInterpretation { fps: Some(30.0), ..frame.interpretation },
+                ..frame
+            });
+        let source = MockSource::new(frames.collect());
+        let convert = |mode: &str| {
+            let yaml = format!("{{type: FpsConvert, fps: 24.0, mode: {mode}}}");
+            let convert =
+                node::<FpsConvert>(&context, &yaml, vec![("input", input(source.clone()))])
+                    .unwrap();
+            let frame_count = convert.get_caps().frame_count;
+            let frames = collect_frames(&context, Arc::new(convert), FrameCount::All).unwrap();
+            assert!(frames.iter().all(|frame| frame.interpretation.fps == Some(24.0)));
+            (frame_count, frames.iter().map(|frame| counter_value(frame)).collect::<Vec<_>>())
+        };
+
+        // every fifth input frame is dropped
+        let (frame_count, frames) = convert("nearest");
+        assert_eq!(frame_count, Some(24));
+        assert_eq!(frames[..5], [0, 8, 24, 32, 40]);
+
+        // the output frame 1 is at input frame 1.25
+        let (frame_count, frames) = convert("blend");
+        assert_eq!(frame_count, Some(24));
+        assert_eq!(frames[..5], [0, 10, 20, 30, 40]);
+
+        let (frame_count, frames) = convert("conform");
+        assert_eq!(frame_count, Some(30));
+        assert_eq!(frames[..3], [0, 8, 16]);
+    }
+
+    #[test]
+    fn test_input_fps() {
+        let context = ProcessingContext::default();
+        // frames that don't know their fps
+        let source = MockSource::new(counter_frames(&context, 0..3));
+        let convert = |mode: &str| {
+            let yaml = format!("{{type: FpsConvert, fps: 24.0, mode: {mode}}}");
+            node::<FpsConvert>(&context, &yaml, vec![("input", input(source.clone()))])
+        };
+
+        let error = convert("nearest").err().unwrap();
+        assert!(error.to_string().contains("set input-fps"), "{error}");
+        let frames =
+            collect_frames(&context, Arc::new(convert("conform").unwrap()), FrameCount::All)
+                .unwrap();
+        assert_eq!(frames.iter().map(|frame| counter_value(frame)).collect::<Vec<_>>(), [0, 1, 2]);
+        assert!(frames.iter().all(|frame| frame.interpretation.fps == Some(24.0)));
+    }
+
+    #[test]
+    fn test_blend_packed_samples() {
+        let a = pack_samples(&[0, 4095, 100], 12);
+        let b = pack_samples(&[4095, 0, 300], 12);
+        let mut out = vec![0; a.len()];
+        blend_samples(&a, &b, 0.5, SampleInterpretation::UInt(12), &mut out).unwrap();
+        assert_eq!(out, pack_samples(&[2048, 2048, 200], 12));
+    }
+}
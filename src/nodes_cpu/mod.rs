@@ -2,5 +2,6 @@
 pub mod benchmark_sink;
 pub mod bitdepth_convert;
 pub mod dual_frame_raw_decoder;
+pub mod fps_convert;
 //pub mod sz3;
 pub mod zstd;
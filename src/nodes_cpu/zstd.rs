@@ -1,9 +1,9 @@
 use crate::{
     pipeline_processing::{
-        frame::{Frame, FrameInterpretation},
-        node::{Caps, EOFError, NodeID, ProcessingNode, Request},
+        frame::{Frame, FrameInterpretation, FrameMetadata},
+        node::{Caps, DroppedError, EOFError, NodeID, ProcessingNode, Request},
         parametrizable::prelude::*,
-        payload::Payload,
+        payload::{Payload, PayloadType},
         processing_context::ProcessingContext,
     },
     util::async_notifier::AsyncNotifier,
@@ -70,9 +70,18 @@ impl ProcessingNode for ZstdBlobReader {
             Result::<_, anyhow::Error>::Ok(())
         })?;
         self.frame_and_file.update(|(frame_no, _)| *frame_no = frame_number + 1);
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
 
-        Ok(Payload::from(Frame { interpretation: self.interpretation.clone(), storage: buffer }))
+        Ok(Payload::from(Frame {
+            interpretation: self.interpretation.clone(),
+            storage: buffer,
+            metadata: FrameMetadata::new(),
+        }))
     }
 
     fn get_caps(&self) -> Caps { Caps { frame_count: None, random_access: false } }
+
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::frame(self.interpretation)) }
 }
@@ -4,7 +4,7 @@ use crate::{
         buffers::GpuBuffer,
         frame::{Frame, FrameInterpretation},
         gpu_util::ensure_gpu_buffer_frame,
-        node::{Caps, InputProcessingNode, NodeID, ProcessingNode, Request},
+        node::{Caps, DroppedError, InputProcessingNode, NodeID, ProcessingNode, Request},
         parametrizable::{
             prelude::*,
             Parameterizable,
@@ -12,7 +12,7 @@ use crate::{
             Parameters,
             ParametersDescriptor,
         },
-        payload::Payload,
+        payload::{Payload, PayloadType},
         processing_context::ProcessingContext,
     },
 };
@@ -46,8 +46,19 @@ pub trait GpuNode: Parameterizable {
         &self,
         frame_interpretation: &FrameInterpretation,
     ) -> Result<HashMap<String, BindingValue>>;
-    fn get_interpretation(&self, frame_interpretation: FrameInterpretation) -> FrameInterpretation {
-        frame_interpretation
+    /// Derives the interpretation of the output frame from the input frame.
+    /// Returns an error if the node cannot process frames of this
+    /// interpretation.
+    fn get_interpretation(frame_interpretation: FrameInterpretation) -> Result<FrameInterpretation>
+    where
+        Self: Sized,
+    {
+        Ok(frame_interpretation)
+    }
+    /// See `ProcessingNode::update_parameters`. `get_binding` is called for
+    /// every frame, so updated values only have to show up there.
+    fn update_parameters(&self, _parameters: Parameters) -> Result<()> {
+        bail!("this node has no live parameters")
     }
 }
 
@@ -90,20 +101,37 @@ impl<T: GpuNode> Parameterizable for GpuNodeImpl<T> {
         Ok(Self { gpu_node, device, pipeline, queue, input })
     }
 
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        T::get_interpretation(*inputs.get("input")?).ok()
+    }
+
     fn get_name() -> String { T::get_name() }
-    fn describe() -> ParameterizableDescriptor { T::describe() }
+    fn describe() -> ParameterizableDescriptor {
+        ParameterizableDescriptor {
+            parameters_descriptor: Self::describe_parameters(),
+            ..T::describe()
+        }
+    }
 }
 
 #[async_trait]
 impl<T: GpuNode + Send + Sync> ProcessingNode for GpuNodeImpl<T> {
     async fn pull(&self, request: Request) -> Result<Payload> {
+        let droppable = request.is_droppable();
         let input = self.input.pull(request).await?;
+        // skip the upload and the compute dispatch
+        if droppable {
+            return Err(DroppedError.into());
+        }
         let (frame, fut) = ensure_gpu_buffer_frame(&input, self.queue.clone())
             .context(format!("Wrong input format for node {}", Self::get_name()))?;
 
-        let mut binding = self.gpu_node.get_binding(&frame.interpretation)?;
+        let output_interpretation = T::get_interpretation(frame.interpretation)?;
 
-        let output_interpretation = self.gpu_node.get_interpretation(frame.interpretation);
+        let mut binding = self.gpu_node.get_binding(&frame.interpretation)?;
 
 
         // TODO: this is racy
@@ -222,8 +250,17 @@ impl<T: GpuNode + Send + Sync> ProcessingNode for GpuNodeImpl<T> {
         Ok(Payload::from(Frame {
             interpretation: output_interpretation,
             storage: GpuBuffer::from(sink_buffer),
+            metadata: frame.metadata.clone(),
         }))
     }
 
     fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input.get_output_type()?.map_frame(T::get_interpretation)
+    }
+
+    fn update_parameters(&self, parameters: Parameters) -> Result<()> {
+        self.gpu_node.update_parameters(parameters)
+    }
 }
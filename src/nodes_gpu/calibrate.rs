@@ -1,15 +1,15 @@
 use crate::pipeline_processing::{
     buffers::GpuBuffer,
-    frame::Frame,
+    frame::{Frame, FrameInterpretation},
     gpu_util::ensure_gpu_buffer_frame,
-    node::{Caps, InputProcessingNode, NodeID, ProcessingNode, Request},
+    node::{Caps, DroppedError, InputProcessingNode, NodeID, ProcessingNode, Request},
     parametrizable::prelude::*,
-    payload::Payload,
+    payload::{Payload, PayloadType},
     processing_context::ProcessingContext,
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use vulkano::{
     buffer::{BufferUsage, DeviceLocalBuffer},
     command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage::OneTimeSubmit},
@@ -108,12 +108,23 @@ impl Parameterizable for Calibrate {
             darkframe_sampler,
         })
     }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        inputs.get("input").copied()
+    }
 }
 
 #[async_trait]
 impl ProcessingNode for Calibrate {
     async fn pull(&self, request: Request) -> Result<Payload> {
+        let droppable = request.is_droppable();
         let input = self.input.pull(request).await?;
+        if droppable {
+            return Err(DroppedError.into());
+        }
 
         let (frame, fut) = ensure_gpu_buffer_frame(&input, self.queue.clone())
             .context("Wrong input format for Calibrate")?;
@@ -179,8 +190,11 @@ impl ProcessingNode for Calibrate {
         Ok(Payload::from(Frame {
             interpretation: frame.interpretation.clone(),
             storage: GpuBuffer::from(sink_buffer),
+            metadata: frame.metadata.clone(),
         }))
     }
 
     fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> { self.input.get_output_type()?.map_frame(Ok) }
 }
@@ -8,21 +8,27 @@ use crate::{
     },
 };
 use anyhow::{bail, Result};
+use parking_lot::RwLock;
 use std::collections::HashMap;
 
 
-pub struct ColorVoodoo {
+#[derive(Clone, Copy)]
+struct Grade {
     pedestal: f32,
     s_gamma: f32,
     v_gamma: f32,
 }
 
+pub struct ColorVoodoo {
+    grade: RwLock<Grade>,
+}
+
 impl Parameterizable for ColorVoodoo {
     fn describe_parameters() -> ParametersDescriptor {
         ParametersDescriptor::new()
-            .with("pedestal", WithDefault(FloatRange(0.0, 1.0), FloatRangeValue(0.0)))
-            .with("s_gamma", WithDefault(FloatRange(0.0, 100.0), FloatRangeValue(1.0)))
-            .with("v_gamma", WithDefault(FloatRange(0.0, 100.0), FloatRangeValue(1.0)))
+            .with_live("pedestal", WithDefault(FloatRange(0.0, 1.0), FloatRangeValue(0.0)))
+            .with_live("s_gamma", WithDefault(FloatRange(0.0, 100.0), FloatRangeValue(1.0)))
+            .with_live("v_gamma", WithDefault(FloatRange(0.0, 100.0), FloatRangeValue(1.0)))
     }
     fn from_parameters(
         mut parameters: Parameters,
@@ -33,9 +39,11 @@ impl Parameterizable for ColorVoodoo {
         Self: Sized,
     {
         Ok(ColorVoodoo {
-            pedestal: parameters.take("pedestal")?,
-            s_gamma: parameters.take("s_gamma")?,
-            v_gamma: parameters.take("v_gamma")?,
+            grade: RwLock::new(Grade {
+                pedestal: parameters.take("pedestal")?,
+                s_gamma: parameters.take("s_gamma")?,
+                v_gamma: parameters.take("v_gamma")?,
+            }),
         })
     }
 }
@@ -45,17 +53,40 @@ impl GpuNode for ColorVoodoo {
 
     fn get_binding(
         &self,
-        frame_interpretation: &FrameInterpretation,
+        _frame_interpretation: &FrameInterpretation,
     ) -> Result<HashMap<String, BindingValue>> {
+        let grade = *self.grade.read();
+        Ok(HashMap::from([
+            ("pedestal".to_string(), BindingValue::F32(grade.pedestal)),
+            ("s_gamma".to_string(), BindingValue::F32(grade.s_gamma)),
+            ("v_gamma".to_string(), BindingValue::F32(grade.v_gamma)),
+        ]))
+    }
+
+    fn get_interpretation(
+        frame_interpretation: FrameInterpretation,
+    ) -> Result<FrameInterpretation> {
         if frame_interpretation.color_interpretation != ColorInterpretation::Rgb {
             bail!("color_voodo node only supports rgb images")
         }
 
-        Ok(HashMap::from([
-            ("pedestal".to_string(), BindingValue::F32(self.pedestal)),
-            ("s_gamma".to_string(), BindingValue::F32(self.s_gamma)),
-            ("v_gamma".to_string(), BindingValue::F32(self.v_gamma)),
-        ]))
+        Ok(frame_interpretation)
+    }
+
+    fn update_parameters(&self, mut parameters: Parameters) -> Result<()> {
+        // all values are taken first, so that a failing update changes nothing
+        let mut grade = *self.grade.read();
+        if let Some(pedestal) = parameters.take_option("pedestal")? {
+            grade.pedestal = pedestal;
+        }
+        if let Some(s_gamma) = parameters.take_option("s_gamma")? {
+            grade.s_gamma = s_gamma;
+        }
+        if let Some(v_gamma) = parameters.take_option("v_gamma")? {
+            grade.v_gamma = v_gamma;
+        }
+        *self.grade.write() = grade;
+        Ok(())
     }
 }
 
@@ -64,12 +95,16 @@ impl GpuNode for ColorVoodoo {
 mod tests {
     use super::ColorVoodoo;
     use crate::{
-        nodes_gpu::base_gpu_node::GpuNodeImpl,
+        nodes_gpu::base_gpu_node::{BindingValue, GpuNode, GpuNodeImpl},
         nodes_util::null_source::NullFrameSource,
         pipeline_processing::{
             frame::{ColorInterpretation, Compression, FrameInterpretation, SampleInterpretation},
             node::{InputProcessingNode, NodeID, ProcessingNode, Request},
-            parametrizable::{prelude::NodeInputValue, Parameterizable, Parameters},
+            parametrizable::{
+                prelude::{FloatRangeValue, NodeInputValue},
+                Parameterizable,
+                Parameters,
+            },
             processing_context::ProcessingContext,
         },
     };
@@ -94,11 +129,39 @@ mod tests {
             }),
         ));
         let parameters = Parameters::new(HashMap::from([("input".to_string(), source)]))
-            .add_defaults(GpuNodeImpl::<ColorVoodoo>::describe_parameters());
+            .add_defaults(GpuNodeImpl::<ColorVoodoo>::describe_parameters())
+            .unwrap();
         let dut = GpuNodeImpl::<ColorVoodoo>::from_parameters(parameters, &[], &context).unwrap();
 
         for _ in 0..10 {
             let _payload = pollster::block_on(dut.pull(Request::new(0, 0))).unwrap();
         }
     }
+
+    #[test]
+    fn test_update_parameters() {
+        let context = ProcessingContext::default();
+        let parameters = Parameters::new(HashMap::new())
+            .add_defaults(ColorVoodoo::describe_parameters())
+            .unwrap();
+        let node = ColorVoodoo::from_parameters(parameters, &[], &context).unwrap();
+
+        node.update_parameters(Parameters::new(HashMap::from([(
+            "pedestal".to_string(),
+            FloatRangeValue(0.25),
+        )])))
+        .unwrap();
+
+        let interpretation = FrameInterpretation {
+            width: 16,
+            height: 16,
+            fps: None,
+            color_interpretation: ColorInterpretation::Rgb,
+            sample_interpretation: SampleInterpretation::FP16,
+            compression: Compression::Uncompressed,
+        };
+        let binding = node.get_binding(&interpretation).unwrap();
+        assert!(matches!(binding["pedestal"], BindingValue::F32(v) if v == 0.25));
+        assert!(matches!(binding["s_gamma"], BindingValue::F32(v) if v == 1.0));
+    }
 }
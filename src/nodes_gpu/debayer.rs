@@ -24,12 +24,18 @@ impl GpuNode for Debayer {
         }
     }
 
-    fn get_interpretation(&self, frame_interpretation: FrameInterpretation) -> FrameInterpretation {
-        FrameInterpretation {
+    fn get_interpretation(
+        frame_interpretation: FrameInterpretation,
+    ) -> Result<FrameInterpretation> {
+        if !matches!(frame_interpretation.color_interpretation, ColorInterpretation::Bayer(_)) {
+            bail!("expected bayer input found {:?}", frame_interpretation.color_interpretation)
+        }
+
+        Ok(FrameInterpretation {
             color_interpretation: ColorInterpretation::Rgb,
             sample_interpretation: SampleInterpretation::UInt(8),
             ..frame_interpretation
-        }
+        })
     }
 }
 
@@ -1,12 +1,12 @@
 use crate::pipeline_processing::{
-    frame::Rgb,
+    frame::{ColorInterpretation, Rgb, SampleInterpretation},
     gpu_util::ensure_gpu_buffer_frame,
     node::{InputProcessingNode, NodeID, ProgressUpdate, SinkNode},
     parametrizable::prelude::*,
     processing_context::ProcessingContext,
-    puller::pull_ordered,
+    puller::{pull_live, pull_ordered, ErrorPolicy, FrameCount},
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use std::{
     convert::TryFrom,
@@ -136,6 +136,7 @@ pub struct Display {
     do_loop: bool,
     input: InputProcessingNode,
     priority: u8,
+    on_error: ErrorPolicy,
 }
 
 impl Parameterizable for Display {
@@ -146,6 +147,7 @@ impl Parameterizable for Display {
             .with("live", Optional(BoolParameter))
             .with("loop", Optional(BoolParameter))
             .with("priority", WithDefault(U8(), ParameterValue::IntRangeValue(0)))
+            .with_error_policy()
             .with("fullscreen", Optional(BoolParameter))
     }
 
@@ -161,6 +163,7 @@ impl Parameterizable for Display {
             fullscreen: parameters.has("fullscreen"),
             input: parameters.take("input")?,
             priority: parameters.take("priority")?,
+            on_error: parameters.get_error_policy()?,
         })
     }
 }
@@ -172,12 +175,15 @@ impl SinkNode for Display {
         context: &ProcessingContext,
         progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
     ) -> Result<()> {
-        let rx = pull_ordered(
+        // in live mode late frames are skipped instead of delaying the following ones
+        let pull = if self.live { pull_live } else { pull_ordered };
+        let rx = pull(
             context,
             self.priority,
             progress_callback,
             self.input.clone_for_same_puller(),
-            0,
+            FrameCount::All,
+            self.on_error,
         );
         let (tx, rx_winit) = flume::bounded(1);
 
@@ -440,6 +446,17 @@ impl SinkNode for Display {
 
         Ok(())
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        if let Some(interpretation) = self.input.get_output_type()?.expect_frame()? {
+            if interpretation.color_interpretation != ColorInterpretation::Rgb
+                || interpretation.sample_interpretation != SampleInterpretation::UInt(8)
+            {
+                bail!("Display needs 8 bit rgb frames, got {interpretation:?}")
+            }
+        }
+        Ok(())
+    }
 }
 
 /// This method is called once during initialization, then again whenever the
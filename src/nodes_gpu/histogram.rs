@@ -2,14 +2,28 @@ use crate::pipeline_processing::{
     buffers::GpuBuffer,
     frame::{Frame, FrameInterpretation, SampleInterpretation},
     gpu_util::ensure_gpu_buffer_frame,
-    node::{Caps, InputProcessingNode, NodeID, ProcessingNode, Request},
+    node::{
+        Caps,
+        Drop,
+        DroppedError,
+        InputProcessingNode,
+        NodeID,
+        PinCache,
+        ProcessingNode,
+        Request,
+    },
     parametrizable::prelude::*,
-    payload::Payload,
+    payload::{Payload, PayloadType},
     processing_context::ProcessingContext,
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use std::sync::Arc;
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc};
 use vulkano::{
     buffer::{BufferUsage, DeviceLocalBuffer},
     command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage::OneTimeSubmit, FillBufferInfo},
@@ -32,17 +46,19 @@ pub struct Histogram {
     device: Arc<Device>,
     pipeline: Arc<ComputePipeline>,
     queue: Arc<Queue>,
-    input: InputProcessingNode,
+    input: Arc<SharedInput>,
 }
 
 impl Parameterizable for Histogram {
+    const OUTPUTS: &'static [&'static str] = &["histogram", "image"];
+
     fn describe_parameters() -> ParametersDescriptor {
         ParametersDescriptor::new().with("input", Mandatory(NodeInputParameter))
     }
 
     fn from_parameters(
         mut parameters: Parameters,
-        _is_input_to: &[NodeID],
+        is_input_to: &[NodeID],
         context: &ProcessingContext,
     ) -> Result<Self>
     where
@@ -61,14 +77,23 @@ impl Parameterizable for Histogram {
         )
         .unwrap();
 
-        Ok(Histogram { device, pipeline, queue, input: parameters.take("input")? })
+        let input = Arc::new(SharedInput {
+            input: Arc::new(parameters.take("input")?),
+            consumers: is_input_to.len(),
+            pulls: Mutex::new(HashMap::new()),
+        });
+        Ok(Histogram { device, pipeline, queue, input })
     }
 }
 
 #[async_trait]
 impl ProcessingNode for Histogram {
     async fn pull(&self, request: Request) -> Result<Payload> {
+        let droppable = request.is_droppable();
         let input = self.input.pull(request).await?;
+        if droppable {
+            return Err(DroppedError.into());
+        }
 
         let (frame, fut) = ensure_gpu_buffer_frame(&input, self.queue.clone())
             .context("Wrong input format for Histogram")?;
@@ -141,8 +166,85 @@ impl ProcessingNode for Histogram {
                 ..frame.interpretation.clone()
             },
             storage: GpuBuffer::from(sink_buffer),
+            metadata: frame.metadata.clone(),
         }))
     }
 
-    fn get_caps(&self) -> Caps { self.input.get_caps() }
+    fn get_caps(&self) -> Caps { self.input.input.get_caps() }
+
+    fn holds_frames_for_consumers(&self) -> bool { self.input.consumers > 1 }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input.input.get_output_type()?.map_frame(|interpretation| {
+            Ok(FrameInterpretation {
+                width: 4096,
+                height: 1,
+                sample_interpretation: SampleInterpretation::FP32,
+                ..interpretation
+            })
+        })
+    }
+
+    fn output_port(&self, name: &str) -> Result<Arc<dyn ProcessingNode + Send + Sync>> {
+        match name {
+            "image" => Ok(Arc::new(HistogramImage { input: self.input.clone() })),
+            _ => bail!("Histogram has no output {name}"),
+        }
+    }
+}
+
+type SharedPull = Shared<BoxFuture<'static, Result<Payload, Arc<anyhow::Error>>>>;
+
+/// The input of `Histogram`, pulled once per frame for all consumers of both
+/// outputs. Like `Cache`, a frame is kept until every consumer pulled it.
+struct SharedInput {
+    input: Arc<InputProcessingNode>,
+    consumers: usize,
+    pulls: Mutex<HashMap<u64, (SharedPull, usize)>>,
+}
+
+impl SharedInput {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        if self.consumers <= 1 {
+            return self.input.pull(request).await;
+        }
+        let frame_number = request.frame_number();
+        let pull = {
+            let mut pulls = self.pulls.lock();
+            let (pull, to_feed) = pulls.entry(frame_number).or_insert_with(|| {
+                let input = self.input.clone();
+                // the payload is shared with the other consumers, so the drop
+                // hint of a single one can't be passed on
+                let request = request.without_extra::<Drop>();
+                let pull = async move { input.pull(request).await.map_err(Arc::new) };
+                (pull.boxed().shared(), self.consumers)
+            });
+            let pull = pull.clone();
+            if request.get_extra::<PinCache>().is_none() {
+                *to_feed -= 1;
+                if *to_feed == 0 {
+                    pulls.remove(&frame_number);
+                }
+            }
+            pull
+        };
+        pull.await.map_err(|error| anyhow!("{error:#}"))
+    }
+}
+
+/// The `image` output of `Histogram`, the frames the histograms are computed
+/// of.
+struct HistogramImage {
+    input: Arc<SharedInput>,
+}
+
+#[async_trait]
+impl ProcessingNode for HistogramImage {
+    async fn pull(&self, request: Request) -> Result<Payload> { self.input.pull(request).await }
+
+    fn get_caps(&self) -> Caps { self.input.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        self.input.input.get_output_type()?.expect_frame().map(PayloadType::Frame)
+    }
 }
@@ -9,6 +9,7 @@ use crate::{
 };
 use anyhow::{anyhow, bail, Result};
 use indoc::indoc;
+use parking_lot::RwLock;
 use std::{
     collections::HashMap,
     fs::File,
@@ -16,20 +17,22 @@ use std::{
     sync::Arc,
 };
 use vulkano::{
-    device::Queue,
+    device::{Device, Queue},
     image::{view::ImageView, ImmutableImage},
     sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
 };
 
 pub struct Lut3d {
-    sampler: BindingValue,
+    sampler: RwLock<BindingValue>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
 }
 
 impl Parameterizable for Lut3d {
     fn describe_parameters() -> ParametersDescriptor {
         ParametersDescriptor::new()
             .with("input", Mandatory(NodeInputParameter))
-            .with("file", Mandatory(StringParameter))
+            .with_live("file", Mandatory(StringParameter))
     }
 
     fn from_parameters(
@@ -43,29 +46,32 @@ impl Parameterizable for Lut3d {
         let (device, queues) = context.require_vulkan()?;
         let queue = queues.iter().find(|&q| q.family().supports_compute()).unwrap().clone();
 
-        let lut_image = read_lut_texture_from_cube_file(parameters.take("file")?, queue.clone())?;
-        let lut_sampler = Sampler::new(
-            device.clone(),
-            SamplerCreateInfo {
-                mag_filter: Filter::Linear,
-                min_filter: Filter::Linear,
-                address_mode: [
-                    SamplerAddressMode::Repeat,
-                    SamplerAddressMode::Repeat,
-                    SamplerAddressMode::Repeat,
-                ],
-                ..Default::default()
-            },
-        )
-        .unwrap();
-
-        let sampler =
-            BindingValue::Sampler((ImageView::new_default(lut_image).unwrap(), lut_sampler));
+        let sampler = load_lut_sampler(parameters.take("file")?, device.clone(), queue.clone())?;
 
-        Ok(Lut3d { sampler })
+        Ok(Lut3d { sampler: RwLock::new(sampler), device, queue })
     }
 }
 
+fn load_lut_sampler(path: String, device: Arc<Device>, queue: Arc<Queue>) -> Result<BindingValue> {
+    let lut_image = read_lut_texture_from_cube_file(path, queue)?;
+    let lut_sampler = Sampler::new(
+        device,
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode: [
+                SamplerAddressMode::Repeat,
+                SamplerAddressMode::Repeat,
+                SamplerAddressMode::Repeat,
+            ],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    Ok(BindingValue::Sampler((ImageView::new_default(lut_image).unwrap(), lut_sampler)))
+}
+
 fn read_cube_size(file_contents: &str) -> Result<usize> {
     for (line_idx, line) in file_contents.lines().enumerate() {
         if line.is_empty() {
@@ -175,13 +181,29 @@ impl GpuNode for Lut3d {
 
     fn get_binding(
         &self,
-        frame_interpretation: &FrameInterpretation,
+        _frame_interpretation: &FrameInterpretation,
     ) -> Result<HashMap<String, BindingValue>> {
+        Ok(HashMap::from([("lut_sampler".to_string(), self.sampler.read().clone())]))
+    }
+
+    fn get_interpretation(
+        frame_interpretation: FrameInterpretation,
+    ) -> Result<FrameInterpretation> {
         if frame_interpretation.color_interpretation != ColorInterpretation::Rgb {
             bail!("Lut3d node only supports rgb images")
         }
 
-        Ok(HashMap::from([("lut_sampler".to_string(), self.sampler.clone())]))
+        Ok(frame_interpretation)
+    }
+
+    fn update_parameters(&self, mut parameters: Parameters) -> Result<()> {
+        if let Some(path) = parameters.take_option("file")? {
+            // the lut is loaded before taking the lock, frames in flight keep
+            // using the old one
+            let sampler = load_lut_sampler(path, self.device.clone(), self.queue.clone())?;
+            *self.sampler.write() = sampler;
+        }
+        Ok(())
     }
 }
 
@@ -227,7 +249,8 @@ mod tests {
             ("input".to_string(), source),
             ("file".to_string(), StringValue("/Users/anuejn/Library/Containers/com.blackmagic-design.DaVinciResolveLite/Data/Library/Application Support/LUT/Film Looks/Rec709 Kodak 2383 D60.cube".to_string())),
         ]))
-            .add_defaults(GpuNodeImpl::<Lut3d>::describe_parameters());
+            .add_defaults(GpuNodeImpl::<Lut3d>::describe_parameters())
+            .unwrap();
         let dut = GpuNodeImpl::<Lut3d>::from_parameters(parameters, &[], &context).unwrap();
 
         for _ in 0..10 {
@@ -4,7 +4,7 @@ use crate::pipeline_processing::{
     node::{InputProcessingNode, NodeID, ProgressUpdate, SinkNode},
     parametrizable::prelude::*,
     processing_context::ProcessingContext,
-    puller::pull_ordered,
+    puller::{pull_ordered, ErrorPolicy, FrameCount},
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -107,6 +107,7 @@ pub struct Plot {
     fullscreen: bool,
     input: InputProcessingNode,
     priority: u8,
+    on_error: ErrorPolicy,
 }
 
 impl Parameterizable for Plot {
@@ -117,6 +118,7 @@ impl Parameterizable for Plot {
             .with("fullscreen", Optional(BoolParameter))
             .with("input", Mandatory(NodeInputParameter))
             .with("priority", WithDefault(U8(), ParameterValue::IntRangeValue(0)))
+            .with_error_policy()
     }
 
     fn from_parameters(
@@ -130,6 +132,7 @@ impl Parameterizable for Plot {
             fullscreen: parameters.has("fullscreen"),
             input: parameters.take("input"),
             priority: parameters.take("priority"),
+            on_error: parameters.get_error_policy()?,
         })
     }
 }
@@ -146,7 +149,8 @@ impl SinkNode for Plot {
             self.priority,
             progress_callback,
             self.input.clone_for_same_puller(),
-            0,
+            FrameCount::All,
+            self.on_error,
         );
         let (tx, rx_winit) = flume::bounded(1);
 
@@ -421,6 +425,10 @@ impl SinkNode for Plot {
 
         Ok(())
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        self.input.get_output_type()?.expect_frame().map(|_| ())
+    }
 }
 
 /// This method is called once during initialization, then again whenever the
@@ -1,5 +1,5 @@
 use crate::{
-    nodes_io::writer_cinema_dng::frame_to_dng_ifd,
+    nodes_io::writer_cinema_dng::{check_dng_interpretation, frame_to_dng_ifd},
     pipeline_processing::{
         node::{InputProcessingNode, NodeID, ProgressUpdate, Request, SinkNode},
         parametrizable::prelude::*,
@@ -114,6 +114,7 @@ impl SinkNode for CinemaDngFrameserver {
         context: &ProcessingContext,
         _progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
     ) -> Result<()> {
+        let shutdown_context = context.clone();
         let context = context.clone();
         let base_ifd = self.base_ifd.clone();
         let priority = self.priority;
@@ -131,7 +132,7 @@ impl SinkNode for CinemaDngFrameserver {
                     .ensure_cpu_buffer_frame(&payload)
                     .context("Wrong input format for CinemaDng")?;
 
-                let ifd = frame_to_dng_ifd(frame, base_ifd)?;
+                let ifd = frame_to_dng_ifd(frame, i, base_ifd)?;
 
                 let mut buffer = Cursor::new(Vec::new());
                 DngWriter::write_dng(&mut buffer, true, FileType::Dng, vec![ifd])?;
@@ -155,11 +156,18 @@ impl SinkNode for CinemaDngFrameserver {
         let server = Server::bind(&self.address).serve(service);
         println!("Listening on http://{}", self.address);
 
-        server.await?;
+        server.with_graceful_shutdown(shutdown_context.cancelled()).await?;
 
 
         Ok::<(), anyhow::Error>(())
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        if let Some(interpretation) = self.input.get_output_type()?.expect_frame()? {
+            check_dng_interpretation(&interpretation)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Derivative)]
@@ -1,29 +1,37 @@
-use crate::pipeline_processing::{
-    frame::{
-        CfaDescriptor,
-        ColorInterpretation,
-        Compression,
-        Frame,
-        FrameInterpretation,
-        SampleInterpretation,
+use crate::{
+    pipeline_processing::{
+        frame::{
+            CfaDescriptor,
+            ColorInterpretation,
+            Compression,
+            Frame,
+            FrameInterpretation,
+            FrameMetadata,
+            SampleInterpretation,
+            SourceFilename,
+            Timecode,
+        },
+        node::{Caps, DroppedError, NodeID, ProcessingNode, Request},
+        parametrizable::prelude::*,
+        payload::{Payload, PayloadType},
+        processing_context::ProcessingContext,
     },
-    node::{Caps, NodeID, ProcessingNode, Request},
-    parametrizable::prelude::*,
-    payload::Payload,
-    processing_context::ProcessingContext,
+    util::lru::{FrameLru, LruStats},
 };
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use dng::{tags, DngReader};
+use dng::{ifd::Ifd, tags, DngReader};
 use glob::glob;
-use std::{fs::File, path::PathBuf, sync::Mutex};
+use std::{fs::File, path::PathBuf};
 
+/// The tags of the first IFD of the DNG file a frame was read from.
+#[derive(Clone)]
+pub struct DngTags(pub Ifd);
 
 pub struct CinemaDngReader {
     files: Vec<PathBuf>,
-    cache_frames: bool,
     internal_loop: bool,
-    cache: Mutex<Vec<Option<Payload>>>,
+    cache: Option<FrameLru>,
     context: ProcessingContext,
 }
 impl Parameterizable for CinemaDngReader {
@@ -33,7 +41,7 @@ impl Parameterizable for CinemaDngReader {
     fn describe_parameters() -> ParametersDescriptor {
         ParametersDescriptor::new()
             .with("file-pattern", Mandatory(StringParameter))
-            .with("cache-frames", Optional(BoolParameter))
+            .with_frame_cache()
             .with("internal-loop", Optional(BoolParameter))
     }
     fn from_parameters(
@@ -52,9 +60,8 @@ impl Parameterizable for CinemaDngReader {
         }
         Ok(Self {
             files,
-            cache_frames: options.has("cache-frames"),
             internal_loop: options.has("internal-loop"),
-            cache: Mutex::new((0..frame_count).map(|_| None).collect()),
+            cache: options.get_frame_cache()?,
             context: context.clone(),
         })
     }
@@ -74,11 +81,14 @@ impl ProcessingNode for CinemaDngReader {
                 self.files.len()
             ));
         }
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
 
-        if self.cache_frames {
-            if let Some(cached) = &self.cache.lock().unwrap()[frame_number as usize] {
-                return Ok(cached.clone());
-            }
+        if let Some(cached) =
+            self.cache.as_ref().and_then(|cache| cache.get_frame(frame_number, &request))
+        {
+            return Ok(cached);
         }
 
         let path = &self.files[frame_number as usize];
@@ -154,10 +164,24 @@ impl ProcessingNode for CinemaDngReader {
             compression: Compression::Uncompressed,
         };
 
-        let payload = Payload::from(Frame { storage: buffer, interpretation });
+        let mut metadata = FrameMetadata::new();
+        metadata.insert(SourceFilename(path.clone()));
+        if let Some(entry) = dng.get_entry_by_path(&main_ifd.chain_tag(tags::ifd::TimeCodes)) {
+            let bytes = entry
+                .value
+                .as_list()
+                .map(|x| x.as_u32().map(|x| x as u8))
+                .collect::<Option<Vec<_>>>();
+            if let Some(timecode) = bytes.as_deref().and_then(Timecode::from_smpte_bytes) {
+                metadata.insert(timecode);
+            }
+        }
+        metadata.insert(DngTags(dng.get_ifd0().clone()));
+
+        let payload = Payload::from(Frame { storage: buffer, interpretation, metadata });
 
-        if self.cache_frames {
-            self.cache.lock().unwrap()[frame_number as usize] = Some(payload.clone());
+        if let Some(cache) = &self.cache {
+            cache.insert_frame(frame_number, payload.clone(), &request);
         }
         Ok(payload)
     }
@@ -168,4 +192,48 @@ impl ProcessingNode for CinemaDngReader {
             random_access: true,
         }
     }
+
+    // the interpretation is only known once the DNG headers are read
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::Frame(None)) }
+
+    fn cache_stats(&self) -> Option<LruStats> { self.cache.as_ref().map(FrameLru::stats) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CinemaDngReader;
+    use crate::{
+        nodes_io::writer_cinema_dng::CinemaDngWriter,
+        pipeline_processing::{processing_context::ProcessingContext, puller::FrameCount},
+        test_util::{bayer_frames, collect_frames, input, node, run_sink, temp_dir, MockSource},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_round_trip() {
+        let context = ProcessingContext::default();
+        let frames = bayer_frames(&context, 2, 25.0);
+        let interpretation = frames[0].interpretation;
+        let written = frames.iter().map(|frame| frame.storage.as_slice(|slice| slice.to_vec()));
+        let written = written.collect::<Vec<_>>();
+
+        let path = temp_dir("dng_round_trip").join("dng");
+        let writer = node::<CinemaDngWriter>(
+            &context,
+            &format!("{{type: CinemaDngWriter, path: {path:?}}}"),
+            vec![("input", input(MockSource::new(frames)))],
+        )
+        .unwrap();
+        run_sink(&context, &writer).unwrap();
+
+        let pattern = path.join("*.dng");
+        let yaml = format!("{{type: CinemaDngReader, file-pattern: {pattern:?}}}");
+        let reader = node::<CinemaDngReader>(&context, &yaml, vec![]).unwrap();
+        let read = collect_frames(&context, Arc::new(reader), FrameCount::All).unwrap();
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(&written) {
+            assert_eq!(read.interpretation, interpretation);
+            read.storage.as_slice(|read| assert_eq!(read, &written[..]));
+        }
+    }
 }
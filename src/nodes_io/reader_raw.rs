@@ -1,9 +1,12 @@
-use crate::pipeline_processing::{
-    frame::{Frame, FrameInterpretation},
-    node::{Caps, NodeID, ProcessingNode, Request},
-    parametrizable::prelude::*,
-    payload::Payload,
-    processing_context::ProcessingContext,
+use crate::{
+    pipeline_processing::{
+        frame::{Frame, FrameInterpretation, FrameMetadata, SourceFilename},
+        node::{Caps, DroppedError, NodeID, ProcessingNode, Request},
+        parametrizable::prelude::*,
+        payload::{Payload, PayloadType},
+        processing_context::ProcessingContext,
+    },
+    util::lru::{FrameLru, LruStats},
 };
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
@@ -19,8 +22,7 @@ use std::{
 pub struct RawBlobReader {
     file: Mutex<File>,
     interpretation: FrameInterpretation,
-    cache_frames: bool,
-    cache: Mutex<Vec<Option<Payload>>>,
+    cache: Option<FrameLru>,
     frame_count: u64,
     context: ProcessingContext,
 }
@@ -32,7 +34,7 @@ impl Parameterizable for RawBlobReader {
         ParametersDescriptor::new()
             .with_interpretation()
             .with("file", Mandatory(StringParameter))
-            .with("cache-frames", Optional(BoolParameter))
+            .with_frame_cache()
     }
     fn from_parameters(
         mut options: Parameters,
@@ -51,8 +53,7 @@ impl Parameterizable for RawBlobReader {
             file: Mutex::new(file),
             interpretation,
             frame_count,
-            cache_frames: options.has("cache-frames"),
-            cache: Mutex::new((0..frame_count).map(|_| None).collect()),
+            cache: options.get_frame_cache()?,
             context: context.clone(),
         })
     }
@@ -68,6 +69,15 @@ impl ProcessingNode for RawBlobReader {
                 self.frame_count
             ));
         }
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
+
+        if let Some(cached) =
+            self.cache.as_ref().and_then(|cache| cache.get_frame(frame_number, &request))
+        {
+            return Ok(cached);
+        }
 
         let mut file = self.file.lock().unwrap();
         file.seek(SeekFrom::Start(frame_number * self.interpretation.required_bytes() as u64))?;
@@ -77,31 +87,33 @@ impl ProcessingNode for RawBlobReader {
         buffer
             .as_mut_slice(|buffer| file.read_exact(buffer).context("error while reading file"))?;
 
-        if self.cache_frames {
-            if let Some(cached) = self.cache.lock().unwrap()[frame_number as usize].clone() {
-                return Ok(cached);
-            }
-        }
+        let payload = Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.interpretation.clone(),
+            metadata: FrameMetadata::new(),
+        });
 
-        let payload =
-            Payload::from(Frame { storage: buffer, interpretation: self.interpretation.clone() });
-
-        self.cache.lock().unwrap()[frame_number as usize] = Some(payload.clone());
+        if let Some(cache) = &self.cache {
+            cache.insert_frame(frame_number, payload.clone(), &request);
+        }
         Ok(payload)
     }
 
     fn get_caps(&self) -> Caps {
         Caps { frame_count: Some(self.frame_count as u64), random_access: true }
     }
+
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::frame(self.interpretation)) }
+
+    fn cache_stats(&self) -> Option<LruStats> { self.cache.as_ref().map(FrameLru::stats) }
 }
 
 
 pub struct RawDirectoryReader {
     files: Vec<PathBuf>,
     interpretation: FrameInterpretation,
-    cache_frames: bool,
     internal_loop: bool,
-    cache: Mutex<Vec<Option<Payload>>>,
+    cache: Option<FrameLru>,
     context: ProcessingContext,
 }
 impl Parameterizable for RawDirectoryReader {
@@ -112,7 +124,7 @@ impl Parameterizable for RawDirectoryReader {
         ParametersDescriptor::new()
             .with_interpretation()
             .with("file-pattern", Mandatory(StringParameter))
-            .with("cache-frames", Optional(BoolParameter))
+            .with_frame_cache()
             .with("internal-loop", Optional(BoolParameter))
     }
     fn from_parameters(
@@ -132,9 +144,8 @@ impl Parameterizable for RawDirectoryReader {
         Ok(Self {
             files,
             interpretation: options.get_interpretation()?,
-            cache_frames: options.has("cache-frames"),
             internal_loop: options.has("internal-loop"),
-            cache: Mutex::new((0..frame_count).map(|_| None).collect()),
+            cache: options.get_frame_cache()?,
             context: context.clone(),
         })
     }
@@ -154,11 +165,14 @@ impl ProcessingNode for RawDirectoryReader {
                 self.files.len()
             ));
         }
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
 
-        if self.cache_frames {
-            if let Some(cached) = &self.cache.lock().unwrap()[frame_number as usize] {
-                return Ok(cached.clone());
-            }
+        if let Some(cached) =
+            self.cache.as_ref().and_then(|cache| cache.get_frame(frame_number, &request))
+        {
+            return Ok(cached);
         }
 
         let path = &self.files[frame_number as usize];
@@ -168,11 +182,16 @@ impl ProcessingNode for RawDirectoryReader {
         buffer
             .as_mut_slice(|buffer| file.read_exact(buffer).context("error while reading file"))?;
 
-        let payload =
-            Payload::from(Frame { storage: buffer, interpretation: self.interpretation.clone() });
+        let mut metadata = FrameMetadata::new();
+        metadata.insert(SourceFilename(path.clone()));
+        let payload = Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.interpretation.clone(),
+            metadata,
+        });
 
-        if self.cache_frames {
-            self.cache.lock().unwrap()[frame_number as usize] = Some(payload.clone());
+        if let Some(cache) = &self.cache {
+            cache.insert_frame(frame_number, payload.clone(), &request);
         }
         Ok(payload)
     }
@@ -183,4 +202,80 @@ impl ProcessingNode for RawDirectoryReader {
             random_access: true,
         }
     }
+
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::frame(self.interpretation)) }
+
+    fn cache_stats(&self) -> Option<LruStats> { self.cache.as_ref().map(FrameLru::stats) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RawBlobReader, RawDirectoryReader};
+    use crate::{
+        nodes_io::writer_raw::{RawBlobWriter, RawDirectoryWriter},
+        pipeline_processing::{
+            buffers::CpuBuffer,
+            frame::Frame,
+            processing_context::ProcessingContext,
+            puller::FrameCount,
+        },
+        test_util::{bayer_frames, collect_frames, input, node, run_sink, temp_dir, MockSource},
+    };
+    use std::sync::Arc;
+
+    // the interpretation of `frames`, as parameters of the readers
+    const INTERPRETATION: &str = "width: 8, height: 4, uint-bits: 12, bayer: GBGR";
+
+    fn frames(context: &ProcessingContext) -> Vec<Frame<CpuBuffer>> {
+        bayer_frames(context, 3, 24.0)
+    }
+
+    fn assert_same_frames(read: &[Arc<Frame<CpuBuffer>>], written: &[Frame<CpuBuffer>]) {
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(written) {
+            assert_eq!(read.interpretation, written.interpretation);
+            read.storage
+                .as_slice(|read| written.storage.as_slice(|written| assert_eq!(read, written)));
+        }
+    }
+
+    #[test]
+    fn test_blob_round_trip() {
+        let context = ProcessingContext::default();
+        let path = temp_dir("blob_round_trip").join("frames.raw12");
+
+        let writer = node::<RawBlobWriter>(
+            &context,
+            &format!("{{type: RawBlobWriter, path: {path:?}}}"),
+            vec![("input", input(MockSource::new(frames(&context))))],
+        )
+        .unwrap();
+        run_sink(&context, &writer).unwrap();
+
+        let yaml = format!("{{type: RawBlobReader, file: {path:?}, {INTERPRETATION}}}");
+        let reader = node::<RawBlobReader>(&context, &yaml, vec![]).unwrap();
+        let read = collect_frames(&context, Arc::new(reader), FrameCount::All).unwrap();
+        assert_same_frames(&read, &frames(&context));
+    }
+
+    #[test]
+    fn test_directory_round_trip() {
+        let context = ProcessingContext::default();
+        let path = temp_dir("directory_round_trip").join("frames");
+
+        let writer = node::<RawDirectoryWriter>(
+            &context,
+            &format!("{{type: RawDirectoryWriter, path: {path:?}}}"),
+            vec![("input", input(MockSource::new(frames(&context))))],
+        )
+        .unwrap();
+        run_sink(&context, &writer).unwrap();
+
+        let pattern = path.join("*.data");
+        let yaml =
+            format!("{{type: RawDirectoryReader, file-pattern: {pattern:?}, {INTERPRETATION}}}");
+        let reader = node::<RawDirectoryReader>(&context, &yaml, vec![]).unwrap();
+        let read = collect_frames(&context, Arc::new(reader), FrameCount::All).unwrap();
+        assert_same_frames(&read, &frames(&context));
+    }
 }
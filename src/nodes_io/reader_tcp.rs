@@ -1,9 +1,9 @@
 use crate::{
     pipeline_processing::{
-        frame::{Frame, FrameInterpretation},
-        node::{Caps, EOFError, NodeID, ProcessingNode, Request},
+        frame::{Frame, FrameInterpretation, FrameMetadata},
+        node::{Caps, DroppedError, EOFError, NodeID, ProcessingNode, Request},
         parametrizable::prelude::*,
-        payload::Payload,
+        payload::{Payload, PayloadType},
         processing_context::ProcessingContext,
     },
     util::async_notifier::AsyncNotifier,
@@ -56,11 +56,22 @@ impl ProcessingNode for TcpReader {
             .context(EOFError)?;
 
         self.notifier.update(|x| *x = frame_number + 1);
+        // the bytes of the frame have to be consumed anyways to stay in sync with the
+        // stream
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
 
-        let payload = Payload::from(Frame { storage: buffer, interpretation: self.interpretation });
+        let payload = Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.interpretation,
+            metadata: FrameMetadata::new(),
+        });
 
         Ok(payload)
     }
 
     fn get_caps(&self) -> Caps { Caps { frame_count: None, random_access: false } }
+
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::frame(self.interpretation)) }
 }
@@ -1,9 +1,16 @@
 use crate::{
     pipeline_processing::{
-        frame::{Frame, FrameInterpretation, Rgb, SampleInterpretation},
-        node::{Caps, NodeID, ProcessingNode, Request},
+        frame::{
+            CaptureTimestamp,
+            Frame,
+            FrameInterpretation,
+            FrameMetadata,
+            Rgb,
+            SampleInterpretation,
+        },
+        node::{Caps, DroppedError, NodeID, ProcessingNode, Request},
         parametrizable::prelude::*,
-        payload::Payload,
+        payload::{Payload, PayloadType},
         processing_context::ProcessingContext,
     },
     util::async_notifier::AsyncNotifier,
@@ -13,6 +20,7 @@ use async_trait::async_trait;
 use std::{
     mem,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 use v4l::{
     buffer::{Metadata, Type},
@@ -95,6 +103,9 @@ impl ProcessingNode for WebcamInput {
         }
         // dbg!(frame_number, metadata.sequence);
         // frame, metadata.sequence
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
 
         let mut buffer =
             unsafe { self.context.get_uninit_cpu_buffer(self.interpretation.required_bytes()) };
@@ -106,10 +117,23 @@ impl ProcessingNode for WebcamInput {
             }
         });
 
-        return Ok(Payload::from(Frame { storage: buffer, interpretation: self.interpretation }));
+        let mut frame_metadata = FrameMetadata::new();
+        frame_metadata.insert(CaptureTimestamp(
+            Duration::from_secs(metadata.timestamp.sec as u64)
+                + Duration::from_micros(metadata.timestamp.usec as u64),
+        ));
+        frame_metadata.insert(metadata);
+
+        return Ok(Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.interpretation,
+            metadata: frame_metadata,
+        }));
     }
 
     fn get_caps(&self) -> Caps { Caps { frame_count: None, random_access: false } }
+
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::frame(self.interpretation)) }
 }
 
 pub struct CpuBufferQueueManager {
@@ -1,10 +1,17 @@
 use crate::pipeline_processing::{
     buffers::CpuBuffer,
-    frame::{ColorInterpretation, Frame, SampleInterpretation},
+    frame::{
+        ColorInterpretation,
+        Frame,
+        FrameInterpretation,
+        SampleInterpretation,
+        SourceFilename,
+        Timecode,
+    },
     node::{InputProcessingNode, NodeID, ProgressUpdate, SinkNode},
     parametrizable::prelude::*,
     processing_context::ProcessingContext,
-    puller::pull_unordered,
+    puller::{pull_unordered, ErrorPolicy, FrameCount},
 };
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
@@ -30,8 +37,9 @@ use std::{
 pub struct CinemaDngWriter {
     dir_path: String,
     input: InputProcessingNode,
-    number_of_frames: Option<u64>,
+    frame_count: FrameCount,
     priority: u8,
+    on_error: ErrorPolicy,
     base_ifd: Ifd,
 }
 
@@ -43,8 +51,9 @@ impl Parameterizable for CinemaDngWriter {
             .with("input", Mandatory(NodeInputParameter))
             .with("path", Mandatory(StringParameter))
             .with("priority", WithDefault(U8(), IntRangeValue(0)))
-            .with("number-of-frames", Optional(NaturalGreaterZero()))
+            .with_frame_count()
             .with("dcp-yaml", Optional(StringParameter))
+            .with_error_policy()
     }
 
     fn from_parameters(
@@ -74,8 +83,9 @@ impl Parameterizable for CinemaDngWriter {
         Ok(Self {
             dir_path: filename,
             input: parameters.take("input")?,
-            number_of_frames: parameters.take_option("number-of-frames")?,
+            frame_count: parameters.get_frame_count()?,
             priority: parameters.take("priority")?,
+            on_error: parameters.get_error_policy()?,
             base_ifd,
         })
     }
@@ -97,13 +107,14 @@ impl SinkNode for CinemaDngWriter {
             self.priority,
             progress_callback,
             self.input.clone_for_same_puller(),
-            self.number_of_frames,
+            self.frame_count,
+            self.on_error,
             move |input, frame_number| {
                 let frame = context
                     .ensure_cpu_buffer_frame(&input)
                     .context("Wrong input format for CinemaDngWriter")?;
 
-                let ifd = frame_to_dng_ifd(frame, base_ifd.clone())?;
+                let ifd = frame_to_dng_ifd(frame, frame_number, base_ifd.clone())?;
 
                 let file = File::create(format!("{}/{:06}.dng", &dir_path, frame_number))?;
                 DngWriter::write_dng(file, true, FileType::Dng, vec![ifd])?;
@@ -113,6 +124,13 @@ impl SinkNode for CinemaDngWriter {
         )
         .await
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        if let Some(interpretation) = self.input.get_output_type()?.expect_frame()? {
+            check_dng_interpretation(&interpretation)?;
+        }
+        Ok(())
+    }
 }
 
 impl Offsets for CpuBuffer {
@@ -123,7 +141,18 @@ impl Offsets for CpuBuffer {
 }
 
 
-pub fn frame_to_dng_ifd(frame: Arc<Frame<CpuBuffer>>, base_ifd: Ifd) -> Result<Ifd> {
+pub fn check_dng_interpretation(interpretation: &FrameInterpretation) -> Result<()> {
+    if !matches!(interpretation.color_interpretation, ColorInterpretation::Bayer(_)) {
+        bail!("cant write non-bayer image as DNG")
+    }
+    Ok(())
+}
+
+pub fn frame_to_dng_ifd(
+    frame: Arc<Frame<CpuBuffer>>,
+    frame_number: u64,
+    base_ifd: Ifd,
+) -> Result<Ifd> {
     let mut ifd = Ifd::new(IfdType::Ifd);
     ifd.insert_from_other(base_ifd);
 
@@ -163,6 +192,22 @@ pub fn frame_to_dng_ifd(frame: Arc<Frame<CpuBuffer>>, base_ifd: Ifd) -> Result<I
         ifd.insert(tags::ifd::FrameRate, IfdValue::SRational((fps * 10000.0) as i32, 10000));
     }
 
+    // prefer the timecode of the source, otherwise count from the first frame
+    let timecode = frame.metadata.get::<Timecode>().copied().or_else(|| {
+        frame.interpretation.fps.map(|fps| Timecode::from_frame_number(frame_number, fps))
+    });
+    if let Some(timecode) = timecode {
+        ifd.insert(tags::ifd::TimeCodes, timecode.to_smpte_bytes());
+    }
+    if let Some(file_name) =
+        frame.metadata.get::<SourceFilename>().and_then(|SourceFilename(path)| path.file_name())
+    {
+        ifd.insert(
+            tags::ifd::OriginalRawFileName,
+            IfdValue::Ascii(file_name.to_string_lossy().into_owned()),
+        );
+    }
+
     ifd.insert(tags::ifd::StripOffsets, IfdValue::Offsets(Arc::new(frame.storage.clone())));
     ifd.insert(tags::ifd::StripByteCounts, frame.storage.len() as u32);
 
@@ -1,16 +1,16 @@
 use crate::pipeline_processing::{
-    frame::{ColorInterpretation, SampleInterpretation},
+    frame::{ColorInterpretation, FrameInterpretation, SampleInterpretation},
     node::{InputProcessingNode, NodeID, ProgressUpdate, SinkNode},
     parametrizable::prelude::*,
     processing_context::ProcessingContext,
-    puller::pull_ordered,
+    puller::{pull_ordered, ErrorPolicy, FrameCount},
 };
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use std::{
     io::Write,
-    process::{Command, Stdio},
-    sync::Arc,
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
 };
 
 pub struct FfmpegWriter {
@@ -18,6 +18,8 @@ pub struct FfmpegWriter {
     input_options: String,
     input: InputProcessingNode,
     priority: u8,
+    on_error: ErrorPolicy,
+    child: Mutex<Option<Child>>,
 }
 impl Parameterizable for FfmpegWriter {
     fn describe_parameters() -> ParametersDescriptor {
@@ -26,6 +28,7 @@ impl Parameterizable for FfmpegWriter {
             .with("output", Mandatory(StringParameter))
             .with("priority", WithDefault(U8(), ParameterValue::IntRangeValue(0)))
             .with("input-options", WithDefault(StringParameter, StringValue("".to_string())))
+            .with_error_policy()
     }
     fn from_parameters(
         mut parameters: Parameters,
@@ -40,6 +43,8 @@ impl Parameterizable for FfmpegWriter {
             input_options: parameters.take("input-options")?,
             input: parameters.take("input")?,
             priority: parameters.take("priority")?,
+            on_error: parameters.get_error_policy()?,
+            child: Mutex::new(None),
         })
     }
 }
@@ -56,35 +61,39 @@ impl SinkNode for FfmpegWriter {
             self.priority,
             progress_callback,
             self.input.clone_for_same_puller(),
-            None,
+            FrameCount::All,
+            self.on_error,
         );
-        let mut frame = context
-            .ensure_cpu_buffer_frame(&rx.recv_async().await.unwrap())
-            .context("Wrong input format for FfmpegWriter")?;
+        let mut frame = match rx.recv_async().await {
+            Ok(payload) => context
+                .ensure_cpu_buffer_frame(&payload)
+                .context("Wrong input format for FfmpegWriter")?,
+            // cancelled or failed before the first frame, the puller already
+            // reported the error
+            Err(_) => return Ok(()),
+        };
 
         let input_options = &self.input_options;
         let fps = frame.interpretation.fps.ok_or(anyhow!("need to know fps to write video"))?;
         let width = frame.interpretation.width;
         let height = frame.interpretation.height;
         let output = &self.output;
-        if !matches!(frame.interpretation.sample_interpretation, SampleInterpretation::UInt(8)) {
-            bail!("A frame with bit_depth=8 is required. Convert the bit depth of the frame!")
-        }
-        let pixel_format = match frame.interpretation.color_interpretation {
-            ColorInterpretation::Bayer(_) => bail!("cant write bayer video with ffmpeg!"),
-            ColorInterpretation::Rgb => "rgb24",
-            ColorInterpretation::Rgba => "rgba",
-        };
+        let pixel_format = pixel_format(&frame.interpretation)?;
 
         let args_string = format!("{input_options} -f rawvideo -framerate {fps} -video_size {width}x{height} -pixel_format {pixel_format} -i - {output}");
 
-        let mut child = Command::new("ffmpeg")
-            .args(shlex::split(&args_string).unwrap())
-            .stdin(Stdio::piped())
-            .spawn()?;
+        let mut command = Command::new("ffmpeg");
+        command.args(shlex::split(&args_string).unwrap()).stdin(Stdio::piped());
+        // keep ffmpeg out of our process group, so that a ctrl-c only stops us
+        // and ffmpeg finishes once its input ends
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command.spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        *self.child.lock().unwrap() = Some(child);
 
         loop {
-            frame.storage.as_slice(|slice| child.stdin.as_mut().unwrap().write_all(slice))?;
+            frame.storage.as_slice(|slice| stdin.write_all(slice))?;
 
             if let Ok(payload) = rx.recv_async().await {
                 frame = context
@@ -96,4 +105,36 @@ impl SinkNode for FfmpegWriter {
         }
         Ok(())
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        if let Some(interpretation) = self.input.get_output_type()?.expect_frame()? {
+            interpretation.fps.ok_or(anyhow!("need to know fps to write video"))?;
+            pixel_format(&interpretation)?;
+        }
+        Ok(())
+    }
+
+    async fn finalize(&self, context: &ProcessingContext) -> Result<()> {
+        // stdin was closed when `run` returned, so ffmpeg sees the end of the
+        // stream and writes the trailer of the container
+        let child = self.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            let status = context.spawn_blocking(move || child.wait()).await??;
+            if !status.success() {
+                bail!("ffmpeg exited with {status}");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pixel_format(interpretation: &FrameInterpretation) -> Result<&'static str> {
+    if !matches!(interpretation.sample_interpretation, SampleInterpretation::UInt(8)) {
+        bail!("A frame with bit_depth=8 is required. Convert the bit depth of the frame!")
+    }
+    match interpretation.color_interpretation {
+        ColorInterpretation::Bayer(_) => bail!("cant write bayer video with ffmpeg!"),
+        ColorInterpretation::Rgb => Ok("rgb24"),
+        ColorInterpretation::Rgba => Ok("rgba"),
+    }
 }
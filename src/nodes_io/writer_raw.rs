@@ -2,7 +2,7 @@ use crate::pipeline_processing::{
     node::{InputProcessingNode, NodeID, ProgressUpdate, SinkNode},
     parametrizable::prelude::*,
     processing_context::ProcessingContext,
-    puller::{pull_ordered, pull_unordered},
+    puller::{pull_ordered, pull_unordered, ErrorPolicy, FrameCount},
 };
 use anyhow::Result;
 use async_trait::async_trait;
@@ -16,8 +16,9 @@ use std::{
 pub struct RawBlobWriter {
     file: Arc<Mutex<File>>,
     input: InputProcessingNode,
-    number_of_frames: Option<u64>,
+    frame_count: FrameCount,
     priority: u8,
+    on_error: ErrorPolicy,
 }
 impl Parameterizable for RawBlobWriter {
     fn describe_parameters() -> ParametersDescriptor {
@@ -25,7 +26,8 @@ impl Parameterizable for RawBlobWriter {
             .with("path", Mandatory(StringParameter))
             .with("input", Mandatory(NodeInputParameter))
             .with("priority", WithDefault(U8(), ParameterValue::IntRangeValue(0)))
-            .with("number-of-frames", Optional(NaturalWithZero()))
+            .with_frame_count()
+            .with_error_policy()
     }
     fn from_parameters(
         mut parameters: Parameters,
@@ -38,8 +40,9 @@ impl Parameterizable for RawBlobWriter {
         Ok(Self {
             file: Arc::new(Mutex::new(File::create(parameters.take::<String>("path")?)?)),
             input: parameters.take("input")?,
-            number_of_frames: parameters.take_option("number-of-frames")?,
+            frame_count: parameters.get_frame_count()?,
             priority: parameters.take("priority")?,
+            on_error: parameters.get_error_policy()?,
         })
     }
 }
@@ -56,7 +59,8 @@ impl SinkNode for RawBlobWriter {
             self.priority,
             progress_callback,
             self.input.clone_for_same_puller(),
-            self.number_of_frames,
+            self.frame_count,
+            self.on_error,
         );
         while let Ok(payload) = rx.recv_async().await {
             let frame = context.ensure_cpu_buffer_frame(&payload)?;
@@ -65,13 +69,25 @@ impl SinkNode for RawBlobWriter {
 
         Ok(())
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        self.input.get_output_type()?.expect_frame().map(|_| ())
+    }
+
+    async fn finalize(&self, _context: &ProcessingContext) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.flush()?;
+        file.sync_all()?;
+        Ok(())
+    }
 }
 
 pub struct RawDirectoryWriter {
     dir_path: String,
     input: InputProcessingNode,
-    number_of_frames: Option<u64>,
+    frame_count: FrameCount,
     priority: u8,
+    on_error: ErrorPolicy,
 }
 impl Parameterizable for RawDirectoryWriter {
     fn describe_parameters() -> ParametersDescriptor {
@@ -79,7 +95,8 @@ impl Parameterizable for RawDirectoryWriter {
             .with("path", Mandatory(StringParameter))
             .with("input", Mandatory(NodeInputParameter))
             .with("priority", WithDefault(U8(), ParameterValue::IntRangeValue(0)))
-            .with("number-of-frames", Optional(NaturalWithZero()))
+            .with_frame_count()
+            .with_error_policy()
     }
 
     fn from_parameters(
@@ -95,8 +112,9 @@ impl Parameterizable for RawDirectoryWriter {
         Ok(Self {
             dir_path: filename,
             input: parameters.take("input")?,
-            number_of_frames: parameters.take_option("number-of-frames")?,
+            frame_count: parameters.get_frame_count()?,
             priority: parameters.take("priority")?,
+            on_error: parameters.get_error_policy()?,
         })
     }
 }
@@ -114,7 +132,8 @@ impl SinkNode for RawDirectoryWriter {
             self.priority,
             progress_callback,
             self.input.clone_for_same_puller(),
-            self.number_of_frames,
+            self.frame_count,
+            self.on_error,
             move |payload, frame_number| {
                 let frame = context_clone.ensure_cpu_buffer_frame(&payload)?;
                 let mut file = File::create(format!("{}/{:06}.data", &dir_path, frame_number))?;
@@ -124,4 +143,8 @@ impl SinkNode for RawDirectoryWriter {
         )
         .await
     }
+
+    fn check_input_types(&self) -> Result<()> {
+        self.input.get_output_type()?.expect_frame().map(|_| ())
+    }
 }
@@ -1,8 +1,9 @@
 use crate::{
     pipeline_processing::{
-        node::{Caps, InputProcessingNode, NodeID, PinCache, ProcessingNode, Request},
+        frame::FrameInterpretation,
+        node::{Caps, Drop, InputProcessingNode, NodeID, PinCache, ProcessingNode, Request},
         parametrizable::prelude::*,
-        payload::Payload,
+        payload::{Payload, PayloadType},
         processing_context::ProcessingContext,
     },
     util::async_notifier::AsyncNotifier,
@@ -40,6 +41,13 @@ impl Parameterizable for Cache {
             capacity,
         })
     }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        inputs.get("input").copied()
+    }
 }
 
 #[async_trait]
@@ -80,7 +88,9 @@ impl ProcessingNode for Cache {
                             } else {
                                 self.nodes_to_feed - 1
                             };
-                            let payload = self.input.pull(request).await?;
+                            // the payload is shared with the other consumers, so the drop
+                            // hint of a single one can't be passed on
+                            let payload = self.input.pull(request.without_extra::<Drop>()).await?;
                             cache.insert(frame_number, (payload.clone(), to_feed));
                             Ok(Some(payload))
                         } else {
@@ -97,4 +107,8 @@ impl ProcessingNode for Cache {
     }
 
     fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn holds_frames_for_consumers(&self) -> bool { self.nodes_to_feed > 1 }
+
+    fn get_output_type(&self) -> Result<PayloadType> { self.input.get_output_type() }
 }
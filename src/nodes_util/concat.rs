@@ -0,0 +1,179 @@
+use crate::pipeline_processing::{
+    buffers::{CpuBuffer, GpuBuffer},
+    frame::{Frame, FrameInterpretation},
+    node::{Caps, EOFError, InputProcessingNode, NodeID, ProcessingNode, Request},
+    parametrizable::{list_input_name, prelude::*},
+    payload::{Payload, PayloadType},
+    processing_context::ProcessingContext,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+pub struct Concat {
+    inputs: Vec<InputProcessingNode>,
+    // the interpretation of the first frame that was pulled and the input it
+    // came from, for inputs that only know their interpretation at runtime
+    seen: Mutex<Option<(usize, FrameInterpretation)>>,
+}
+
+impl Parameterizable for Concat {
+    const DESCRIPTION: Option<&'static str> =
+        Some("plays its inputs one after another, all of them have to produce the same frames");
+
+    fn describe_parameters() -> ParametersDescriptor {
+        ParametersDescriptor::new()
+            .with("inputs", Mandatory(ListParameter(Box::new(NodeInputParameter))))
+    }
+
+    fn from_parameters(
+        mut parameters: Parameters,
+        _is_input_to: &[NodeID],
+        _context: &ProcessingContext,
+    ) -> Result<Self> {
+        let inputs: Vec<InputProcessingNode> = parameters.take_vec("inputs")?;
+        if inputs.is_empty() {
+            bail!("Concat needs at least one input");
+        }
+        for (i, input) in inputs.iter().enumerate().rev().skip(1) {
+            if input.get_caps().frame_count.is_none() {
+                bail!(
+                    "input {i} doesn't know how many frames it has, only the last input can be \
+                     endless"
+                );
+            }
+        }
+        Ok(Self { inputs, seen: Mutex::new(None) })
+    }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        inputs.get(&list_input_name("inputs", 0)).copied()
+    }
+}
+
+/// Frames of different inputs can be concatenated if they only differ in a
+/// frame rate that is not known for one of them.
+fn check_compatible(
+    (first_input, first): (usize, FrameInterpretation),
+    (input, interpretation): (usize, FrameInterpretation),
+) -> Result<()> {
+    let fps_matches = match (first.fps, interpretation.fps) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    };
+    let same_frames = FrameInterpretation { fps: first.fps, ..interpretation } == first;
+    if !fps_matches || !same_frames {
+        bail!(
+            "input {input} of Concat produces {interpretation} frames, which don't match the \
+             {first} frames of input {first_input}"
+        );
+    }
+    Ok(())
+}
+
+fn interpretation_of(payload: &Payload) -> Option<FrameInterpretation> {
+    payload
+        .downcast_ref::<Frame<CpuBuffer>>()
+        .map(|frame| frame.interpretation)
+        .or_else(|| payload.downcast_ref::<Frame<GpuBuffer>>().map(|frame| frame.interpretation))
+}
+
+#[async_trait]
+impl ProcessingNode for Concat {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        let mut frame_number = request.frame_number();
+        for (i, input) in self.inputs.iter().enumerate() {
+            match input.get_caps().frame_count {
+                Some(frame_count) if frame_number >= frame_count => frame_number -= frame_count,
+                _ => {
+                    let payload = input.pull(request.with_frame_number(frame_number)).await?;
+                    if let Some(interpretation) = interpretation_of(&payload) {
+                        let first = *self.seen.lock().get_or_insert((i, interpretation));
+                        check_compatible(first, (i, interpretation))?;
+                    }
+                    return Ok(payload);
+                }
+            }
+        }
+        Err(EOFError.into())
+    }
+
+    fn get_caps(&self) -> Caps {
+        let caps = self.inputs.iter().map(|input| input.get_caps()).collect::<Vec<_>>();
+        Caps {
+            frame_count: caps.iter().map(|caps| caps.frame_count).sum(),
+            random_access: caps.iter().all(|caps| caps.random_access),
+        }
+    }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        let mut first = None;
+        for (i, input) in self.inputs.iter().enumerate() {
+            if let Some(interpretation) = input.get_output_type()?.expect_frame()? {
+                check_compatible(*first.get_or_insert((i, interpretation)), (i, interpretation))?;
+            }
+        }
+        Ok(PayloadType::Frame(first.map(|(_, interpretation)| interpretation)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Concat;
+    use crate::{
+        pipeline_processing::{
+            frame::{ColorInterpretation, FrameInterpretation},
+            node::ProcessingNode,
+            processing_context::ProcessingContext,
+            puller::FrameCount,
+        },
+        test_util::{
+            collect_frames,
+            counter_frames,
+            counter_value,
+            frame,
+            input,
+            node,
+            MockSource,
+        },
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_concat() {
+        let context = ProcessingContext::default();
+        let source =
+            |values: std::ops::Range<u8>| input(MockSource::new(counter_frames(&context, values)));
+        let concat = node::<Concat>(
+            &context,
+            "{type: Concat}",
+            vec![("inputs[1]", source(10..12)), ("inputs[0]", source(0..3))],
+        )
+        .unwrap();
+        assert_eq!(concat.get_caps().frame_count, Some(5));
+        let frames = collect_frames(&context, Arc::new(concat), FrameCount::All).unwrap();
+        let frames = frames.iter().map(|frame| counter_value(frame));
+        assert_eq!(frames.collect::<Vec<_>>(), [0, 1, 2, 10, 11]);
+
+        let rgb = counter_frames(&context, [0]).remove(0);
+        let rgba = FrameInterpretation {
+            color_interpretation: ColorInterpretation::Rgba,
+            ..rgb.interpretation
+        };
+        let concat = node::<Concat>(
+            &context,
+            "{type: Concat}",
+            vec![
+                ("inputs[0]", input(MockSource::new(vec![rgb]))),
+                ("inputs[1]", input(MockSource::new(vec![frame(&context, rgba, &[1; 4])]))),
+            ],
+        )
+        .unwrap();
+        let error = concat.get_output_type().unwrap_err().to_string();
+        assert!(error.contains("input 1 of Concat"), "{error}");
+    }
+}
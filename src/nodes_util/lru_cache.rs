@@ -0,0 +1,104 @@
+use crate::{
+    pipeline_processing::{
+        frame::FrameInterpretation,
+        node::{Caps, InputProcessingNode, NodeID, ProcessingNode, Request},
+        parametrizable::prelude::*,
+        payload::{Payload, PayloadType},
+        processing_context::ProcessingContext,
+    },
+    util::lru::{FrameLru, LruStats},
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+pub struct LruCache {
+    input: InputProcessingNode,
+    cache: FrameLru,
+}
+
+impl Parameterizable for LruCache {
+    const DESCRIPTION: Option<&'static str> = Some(
+        "keeps the most recently used frames of its input up to max-bytes, for inputs whose \
+         frames are requested again, like when seeking back and forth",
+    );
+
+    fn describe_parameters() -> ParametersDescriptor {
+        ParametersDescriptor::new()
+            .with("input", Mandatory(NodeInputParameter))
+            .with("max-bytes", WithDefault(NaturalGreaterZero(), IntRangeValue(1 << 30)))
+    }
+
+    fn from_parameters(
+        mut parameters: Parameters,
+        _is_input_to: &[NodeID],
+        _context: &ProcessingContext,
+    ) -> Result<Self> {
+        let max_bytes: u64 = parameters.take("max-bytes")?;
+        Ok(Self { input: parameters.take("input")?, cache: FrameLru::new(max_bytes as usize) })
+    }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        inputs.get("input").copied()
+    }
+}
+
+#[async_trait]
+impl ProcessingNode for LruCache {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        let frame_number = request.frame_number();
+        if let Some(cached) = self.cache.get_frame(frame_number, &request) {
+            return Ok(cached);
+        }
+        let payload = self.input.pull(request.clone()).await?;
+        self.cache.insert_frame(frame_number, payload.clone(), &request);
+        Ok(payload)
+    }
+
+    fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> { self.input.get_output_type() }
+
+    fn cache_stats(&self) -> Option<LruStats> { Some(self.cache.stats()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+    use crate::{
+        pipeline_processing::{
+            node::{PinCache, ProcessingNode, Request},
+            processing_context::ProcessingContext,
+        },
+        test_util::{counter_frames, counter_value, input, node, MockSource},
+    };
+
+    #[test]
+    fn test_lru_cache() {
+        let context = ProcessingContext::default();
+        let source = MockSource::new(counter_frames(&context, 0..10));
+        // room for two frames of three bytes
+        let cache = node::<LruCache>(
+            &context,
+            "{type: LruCache, max-bytes: 6}",
+            vec![("input", input(source.clone()))],
+        )
+        .unwrap();
+        let pull = |request: Request| {
+            let payload = context.block_on(cache.pull(request)).unwrap();
+            counter_value(&context.ensure_cpu_buffer_frame(&payload).unwrap())
+        };
+
+        assert_eq!(pull(Request::new(0, 0).with_extra(PinCache)), 0);
+        for frame_number in [1, 2, 3, 0, 3] {
+            assert_eq!(pull(Request::new(0, frame_number)), frame_number as u8);
+        }
+        // 0 was pinned, so only 1 and 2 were evicted
+        assert_eq!(source.requests(), [0, 1, 2, 3]);
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 4, 2));
+    }
+}
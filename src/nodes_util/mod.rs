@@ -1,3 +1,8 @@
 pub mod cache;
+pub mod concat;
+pub mod lru_cache;
 pub mod null_source;
+pub mod prefetch;
+pub mod range;
 pub mod split;
+pub mod test_pattern;
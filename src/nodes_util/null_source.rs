@@ -1,8 +1,8 @@
 use crate::pipeline_processing::{
-    frame::{Frame, FrameInterpretation},
-    node::{Caps, NodeID, ProcessingNode, Request},
+    frame::{Frame, FrameInterpretation, FrameMetadata},
+    node::{Caps, DroppedError, NodeID, ProcessingNode, Request},
     parametrizable::{Parameterizable, Parameters, ParametersDescriptor},
-    payload::Payload,
+    payload::{Payload, PayloadType},
     processing_context::ProcessingContext,
 };
 use async_trait::async_trait;
@@ -34,7 +34,10 @@ impl Parameterizable for NullFrameSource {
 
 #[async_trait]
 impl ProcessingNode for NullFrameSource {
-    async fn pull(&self, _request: Request) -> anyhow::Result<Payload> {
+    async fn pull(&self, request: Request) -> anyhow::Result<Payload> {
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
         let buffer = unsafe {
             let mut buffer =
                 self.context.get_uninit_cpu_buffer(self.interpretation.required_bytes());
@@ -44,9 +47,15 @@ impl ProcessingNode for NullFrameSource {
             buffer
         };
 
-        let payload =
-            Payload::from(Frame { storage: buffer, interpretation: self.interpretation.clone() });
+        let payload = Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.interpretation.clone(),
+            metadata: FrameMetadata::new(),
+        });
         Ok(payload)
     }
     fn get_caps(&self) -> Caps { Caps { frame_count: None, random_access: true } }
+    fn get_output_type(&self) -> anyhow::Result<PayloadType> {
+        Ok(PayloadType::frame(self.interpretation))
+    }
 }
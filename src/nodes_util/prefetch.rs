@@ -0,0 +1,187 @@
+use crate::pipeline_processing::{
+    frame::FrameInterpretation,
+    node::{Caps, Drop, InputProcessingNode, NodeID, ProcessingNode, Request},
+    parametrizable::prelude::*,
+    payload::{Payload, PayloadType},
+    processing_context::ProcessingContext,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+pub struct Prefetch {
+    input: Arc<InputProcessingNode>,
+    context: ProcessingContext,
+    frames: u64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    // the highest frame that was requested since the last seek
+    position: Option<u64>,
+    // whether the frames after position are requested in order
+    sequential: bool,
+    // dropping one of these cancels the pull
+    in_flight: BTreeMap<u64, BoxFuture<'static, Result<Payload>>>,
+}
+
+impl Parameterizable for Prefetch {
+    const DESCRIPTION: Option<&'static str> = Some(
+        "pulls the next frames of its input ahead of time while they are requested in order, \
+         for readers that block while loading a frame",
+    );
+
+    fn describe_parameters() -> ParametersDescriptor {
+        ParametersDescriptor::new()
+            .with("input", Mandatory(NodeInputParameter))
+            .with("frames", WithDefault(NaturalGreaterZero(), IntRangeValue(4)))
+    }
+
+    fn from_parameters(
+        mut parameters: Parameters,
+        _is_input_to: &[NodeID],
+        context: &ProcessingContext,
+    ) -> Result<Self> {
+        let input: InputProcessingNode = parameters.take("input")?;
+        if !input.get_caps().random_access {
+            bail!("the input doesn't support random access, it can't be read ahead");
+        }
+        Ok(Self {
+            input: Arc::new(input),
+            context: context.clone(),
+            frames: parameters.take("frames")?,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        inputs.get("input").copied()
+    }
+}
+
+impl Prefetch {
+    /// Updates the observed access pattern with a request for `frame_number`
+    /// and returns the pull of that frame if it was already started.
+    fn observe(&self, request: &Request) -> Option<BoxFuture<'static, Result<Payload>>> {
+        let frame_number = request.frame_number();
+        let mut state = self.state.lock();
+        let hit = state.in_flight.remove(&frame_number);
+
+        match state.position {
+            Some(position)
+                if frame_number > position && frame_number <= position + self.frames + 1 =>
+            {
+                state.position = Some(frame_number);
+                state.sequential = true;
+            }
+            // late requests of frames before the position don't change the pattern
+            Some(position)
+                if frame_number <= position && frame_number + self.frames >= position => {}
+            _ => {
+                state.position = Some(frame_number);
+                state.sequential = false;
+            }
+        }
+        let position = state.position.unwrap();
+        // frames that were skipped and everything of the old position after a seek
+        state.in_flight.retain(|&frame, _| frame > position && frame <= position + self.frames);
+
+        if state.sequential {
+            let end = match self.input.get_caps().frame_count {
+                Some(frame_count) => frame_count.min(position + self.frames + 1),
+                None => position + self.frames + 1,
+            };
+            for frame in position + 1..end {
+                if state.in_flight.contains_key(&frame) {
+                    continue;
+                }
+                // the frames are read for later requests, so the drop hint of this one
+                // doesn't apply to them
+                let speculative = request.with_frame_number(frame).without_extra::<Drop>();
+                let input = self.input.clone();
+                let pull = self.context.spawn(request.priority().for_frame(frame), async move {
+                    input.pull(speculative).await
+                });
+                state.in_flight.insert(frame, Box::pin(pull));
+            }
+        }
+
+        hit
+    }
+}
+
+#[async_trait]
+impl ProcessingNode for Prefetch {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        match self.observe(&request) {
+            Some(pull) => pull.await,
+            None => self.input.pull(request).await,
+        }
+    }
+
+    fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> { self.input.get_output_type() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Prefetch;
+    use crate::{
+        pipeline_processing::{
+            node::{ProcessingNode, Request},
+            processing_context::ProcessingContext,
+        },
+        test_util::{counter_frames, counter_value, input, node, MockSource},
+    };
+
+    #[test]
+    fn test_prefetch() {
+        let context = ProcessingContext::default();
+        let source = MockSource::new(counter_frames(&context, 0..30));
+        let prefetch = node::<Prefetch>(
+            &context,
+            "{type: Prefetch, frames: 2}",
+            vec![("input", input(source.clone()))],
+        )
+        .unwrap();
+        let pull = |frame_number: u64| {
+            let payload = context.block_on(prefetch.pull(Request::new(0, frame_number))).unwrap();
+            let frame = context.ensure_cpu_buffer_frame(&payload).unwrap();
+            assert_eq!(counter_value(&frame), frame_number as u8);
+        };
+        let in_flight = || prefetch.state.lock().in_flight.keys().copied().collect::<Vec<_>>();
+
+        pull(0);
+        assert_eq!(in_flight(), []);
+        for frame_number in 1..6 {
+            pull(frame_number);
+            assert_eq!(in_flight(), [frame_number + 1, frame_number + 2]);
+        }
+        // every frame was only read once, the later ones ahead of time
+        let requests = source.requests();
+        for frame_number in 0..6 {
+            assert_eq!(requests.iter().filter(|n| **n == frame_number).count(), 1);
+        }
+
+        // a seek cancels the reads and waits until the pattern is sequential again
+        pull(20);
+        assert_eq!(in_flight(), []);
+        pull(21);
+        assert_eq!(in_flight(), [22, 23]);
+        // no reads past the end
+        for frame_number in 22..29 {
+            pull(frame_number);
+        }
+        assert_eq!(in_flight(), [29]);
+    }
+}
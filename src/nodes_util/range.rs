@@ -0,0 +1,141 @@
+use crate::pipeline_processing::{
+    frame::FrameInterpretation,
+    node::{Caps, EOFError, InputProcessingNode, NodeID, ProcessingNode, Request},
+    parametrizable::prelude::*,
+    payload::{Payload, PayloadType},
+    processing_context::ProcessingContext,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+pub struct Range {
+    input: InputProcessingNode,
+    start: u64,
+    end: Option<u64>,
+    step: u64,
+    reverse: bool,
+}
+
+impl Parameterizable for Range {
+    const DESCRIPTION: Option<&'static str> = Some(
+        "selects the frames start, start + step, ... before end (exclusive) of its input, \
+         optionally in reverse order",
+    );
+
+    fn describe_parameters() -> ParametersDescriptor {
+        ParametersDescriptor::new()
+            .with("input", Mandatory(NodeInputParameter))
+            .with("start", WithDefault(NaturalWithZero(), IntRangeValue(0)))
+            .with("end", Optional(NaturalWithZero()))
+            .with("step", WithDefault(NaturalGreaterZero(), IntRangeValue(1)))
+            .with("reverse", Flag())
+    }
+
+    fn from_parameters(
+        mut parameters: Parameters,
+        _is_input_to: &[NodeID],
+        _context: &ProcessingContext,
+    ) -> Result<Self> {
+        let input: InputProcessingNode = parameters.take("input")?;
+        let start = parameters.take("start")?;
+        let step = parameters.take("step")?;
+        let reverse = parameters.take("reverse")?;
+
+        let caps = input.get_caps();
+        let end = match (parameters.take_option::<u64>("end")?, caps.frame_count) {
+            (Some(end), Some(frame_count)) => Some(end.min(frame_count)),
+            (end, frame_count) => end.or(frame_count),
+        };
+        if let Some(end) = end {
+            if start >= end {
+                bail!("the range {start}..{end} contains no frames of the input");
+            }
+        }
+        if reverse && end.is_none() {
+            bail!("reverse needs an end, the input doesn't know how many frames it has");
+        }
+        if !caps.random_access && (start != 0 || step != 1 || reverse) {
+            bail!(
+                "the input doesn't support random access, only end can be set to select its \
+                 first frames"
+            );
+        }
+
+        Ok(Self { input, start, end, step, reverse })
+    }
+
+    fn describe_output(
+        _parameters: Parameters,
+        inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        inputs.get("input").copied()
+    }
+}
+
+impl Range {
+    fn frame_count(&self) -> Option<u64> {
+        self.end.map(|end| (end - self.start + self.step - 1) / self.step)
+    }
+}
+
+#[async_trait]
+impl ProcessingNode for Range {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        let mut index = request.frame_number();
+        if let Some(frame_count) = self.frame_count() {
+            if index >= frame_count {
+                return Err(EOFError.into());
+            }
+            if self.reverse {
+                index = frame_count - 1 - index;
+            }
+        }
+        self.input.pull(request.with_frame_number(self.start + index * self.step)).await
+    }
+
+    fn get_caps(&self) -> Caps { Caps { frame_count: self.frame_count(), ..self.input.get_caps() } }
+
+    fn get_output_type(&self) -> Result<PayloadType> { self.input.get_output_type() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Range;
+    use crate::{
+        pipeline_processing::{
+            node::ProcessingNode,
+            processing_context::ProcessingContext,
+            puller::FrameCount,
+        },
+        test_util::{collect_frames, counter_frames, counter_value, input, node, MockSource},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_range() {
+        let context = ProcessingContext::default();
+        let source = MockSource::new(counter_frames(&context, 0..10));
+        let range = |yaml: &str| {
+            let range =
+                node::<Range>(&context, yaml, vec![("input", input(source.clone()))]).unwrap();
+            let frame_count = range.get_caps().frame_count;
+            let frames = collect_frames(&context, Arc::new(range), FrameCount::All).unwrap();
+            (frame_count, frames.iter().map(|frame| counter_value(frame)).collect::<Vec<_>>())
+        };
+
+        assert_eq!(range("{type: Range, start: 2, end: 9, step: 3}"), (Some(3), vec![2, 5, 8]));
+        assert_eq!(
+            range("{type: Range, start: 2, end: 9, step: 3, reverse: true}"),
+            (Some(3), vec![8, 5, 2])
+        );
+        // the end is clamped to the length of the input
+        assert_eq!(range("{type: Range, start: 7, end: 100}"), (Some(3), vec![7, 8, 9]));
+        assert!(node::<Range>(
+            &context,
+            "{type: Range, start: 10}",
+            vec![("input", input(source.clone()))]
+        )
+        .is_err());
+    }
+}
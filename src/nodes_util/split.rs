@@ -1,13 +1,16 @@
 use crate::pipeline_processing::{
     node::{Caps, InputProcessingNode, NodeID, ProcessingNode, Request},
     parametrizable::{prelude::*, Parameterizable, Parameters, ParametersDescriptor},
-    payload::Payload,
+    payload::{Payload, PayloadType},
     processing_context::ProcessingContext,
 };
 use anyhow::Result;
 use async_trait::async_trait;
 
 
+/// Selects an element of a `Vec<Payload>`. Kept for nodes that still return
+/// lists, new nodes should declare named outputs instead (see
+/// `Parameterizable::OUTPUTS`).
 pub struct Split {
     input: InputProcessingNode,
     elem: i64,
@@ -46,4 +49,17 @@ impl ProcessingNode for Split {
     }
 
     fn get_caps(&self) -> Caps { self.input.get_caps() }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        match self.input.get_output_type()?.expect_list()? {
+            Some(elements) => elements.get(self.elem as usize).cloned().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "tried to get element {} of a list with {} elements",
+                    self.elem,
+                    elements.len()
+                )
+            }),
+            None => Ok(PayloadType::Unknown),
+        }
+    }
 }
@@ -0,0 +1,307 @@
+use crate::pipeline_processing::{
+    frame::{ColorInterpretation, Frame, FrameInterpretation, FrameMetadata, SampleInterpretation},
+    node::{Caps, DroppedError, NodeID, ProcessingNode, Request},
+    parametrizable::prelude::*,
+    payload::{Payload, PayloadType},
+    processing_context::ProcessingContext,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pattern {
+    Bars,
+    Ramp,
+    Checkerboard,
+    Counter,
+    Noise,
+}
+
+impl Pattern {
+    fn is_static(&self) -> bool {
+        matches!(self, Pattern::Bars | Pattern::Ramp | Pattern::Checkerboard)
+    }
+}
+
+// the classic color bars, at full intensity
+const BARS: [[f32; 3]; 8] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0],
+    [0.0, 1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 0.0, 0.0],
+];
+
+pub struct TestPatternSource {
+    context: ProcessingContext,
+    interpretation: FrameInterpretation,
+    pattern: Pattern,
+    seed: u64,
+    square_size: u64,
+    // the rendered frame of patterns that don't change over time
+    static_frame: Mutex<Option<Vec<u8>>>,
+}
+
+impl Parameterizable for TestPatternSource {
+    const DESCRIPTION: Option<&'static str> = Some(
+        "generates deterministic test patterns with the specified interpretation: color bars, \
+         ramps, a checkerboard, a moving box with the frame number or seeded noise",
+    );
+
+    fn describe_parameters() -> ParametersDescriptor {
+        ParametersDescriptor::new()
+            .with_interpretation()
+            .with(
+                "pattern",
+                WithDefault(
+                    Choice(&["bars", "ramp", "checkerboard", "counter", "noise"]),
+                    StringValue("bars".to_string()),
+                ),
+            )
+            .with("seed", WithDefault(NaturalWithZero(), IntRangeValue(0)))
+            .with("square-size", WithDefault(NaturalGreaterZero(), IntRangeValue(16)))
+    }
+
+    fn from_parameters(
+        mut parameters: Parameters,
+        _is_input_to: &[NodeID],
+        context: &ProcessingContext,
+    ) -> Result<Self> {
+        let pattern = match parameters.take::<String>("pattern")?.to_lowercase().as_str() {
+            "bars" => Pattern::Bars,
+            "ramp" => Pattern::Ramp,
+            "checkerboard" => Pattern::Checkerboard,
+            "counter" => Pattern::Counter,
+            "noise" => Pattern::Noise,
+            other => bail!("unknown pattern {other}"),
+        };
+        Ok(Self {
+            context: context.clone(),
+            interpretation: parameters.get_interpretation()?,
+            pattern,
+            seed: parameters.take("seed")?,
+            square_size: parameters.take("square-size")?,
+            static_frame: Mutex::new(None),
+        })
+    }
+}
+
+impl TestPatternSource {
+    /// The color of a pixel, every channel in `0..=1`.
+    fn color(&self, x: u64, y: u64, frame_number: u64) -> [f32; 3] {
+        let FrameInterpretation { width, height, .. } = self.interpretation;
+        match self.pattern {
+            Pattern::Bars => BARS[(x * BARS.len() as u64 / width) as usize],
+            Pattern::Ramp => {
+                // gray, red, green and blue ramps from top to bottom
+                let value = x as f32 / (width.max(2) - 1) as f32;
+                match y * 4 / height {
+                    0 => [value; 3],
+                    band => {
+                        let mut color = [0.0; 3];
+                        color[band as usize - 1] = value;
+                        color
+                    }
+                }
+            }
+            Pattern::Checkerboard => {
+                let square = x / self.square_size + y / self.square_size;
+                [if square % 2 == 0 { 1.0 } else { 0.0 }; 3]
+            }
+            Pattern::Counter => {
+                // a box moving one square per frame, showing the 16 lowest
+                // bits of the frame number as a 4x4 grid of cells, most
+                // significant bit first
+                let size = self.square_size * 4;
+                let box_x = frame_number * self.square_size % width.saturating_sub(size).max(1);
+                let box_y = height.saturating_sub(size) / 2;
+                if (box_x..box_x + size).contains(&x) && (box_y..box_y + size).contains(&y) {
+                    let cell = (y - box_y) / self.square_size * 4 + (x - box_x) / self.square_size;
+                    [((frame_number >> (15 - cell)) & 1) as f32; 3]
+                } else {
+                    [0.5; 3]
+                }
+            }
+            Pattern::Noise => {
+                let hash = |channel: u64| {
+                    let index = (frame_number * height + y) * width + x;
+                    let random = split_mix(self.seed ^ split_mix(index * 3 + channel));
+                    (random >> 40) as f32 / (1u64 << 24) as f32
+                };
+                [hash(0), hash(1), hash(2)]
+            }
+        }
+    }
+
+    fn render(&self, frame_number: u64, out: &mut [u8]) {
+        let interpretation = &self.interpretation;
+        let samples = (0..interpretation.height).flat_map(|y| {
+            (0..interpretation.width).flat_map(move |x| {
+                let [r, g, b] = self.color(x, y, frame_number);
+                let (channels, n) = match interpretation.color_interpretation {
+                    ColorInterpretation::Bayer(cfa) => {
+                        let red_row = (y % 2 == 0) == cfa.red_in_first_row;
+                        let red_col = (x % 2 == 0) == cfa.red_in_first_col;
+                        match (red_row, red_col) {
+                            (true, true) => ([r, 0.0, 0.0, 0.0], 1),
+                            (false, false) => ([b, 0.0, 0.0, 0.0], 1),
+                            _ => ([g, 0.0, 0.0, 0.0], 1),
+                        }
+                    }
+                    ColorInterpretation::Rgb => ([r, g, b, 0.0], 3),
+                    ColorInterpretation::Rgba => ([r, g, b, 1.0], 4),
+                };
+                channels.into_iter().take(n)
+            })
+        });
+        write_samples(samples, interpretation.sample_interpretation, out);
+    }
+}
+
+/// Writes samples in `0..=1` with the given interpretation. Integer samples are
+/// packed most significant bit first, like the raw formats of the camera.
+fn write_samples(
+    samples: impl Iterator<Item = f32>,
+    interpretation: SampleInterpretation,
+    out: &mut [u8],
+) {
+    match interpretation {
+        SampleInterpretation::UInt(bits) => {
+            let max = ((1u64 << bits) - 1) as f32;
+            let mut accumulator = 0u64;
+            let mut accumulated_bits = 0;
+            let mut pos = 0;
+            for sample in samples {
+                accumulator = (accumulator << bits) | (sample * max).round() as u64;
+                accumulated_bits += bits as u32;
+                while accumulated_bits >= 8 {
+                    accumulated_bits -= 8;
+                    out[pos] = (accumulator >> accumulated_bits) as u8;
+                    pos += 1;
+                }
+                accumulator &= (1 << accumulated_bits) - 1;
+            }
+            if accumulated_bits > 0 && pos < out.len() {
+                out[pos] = (accumulator << (8 - accumulated_bits)) as u8;
+            }
+        }
+        SampleInterpretation::FP16 => {
+            for (sample, out) in samples.zip(out.chunks_exact_mut(2)) {
+                out.copy_from_slice(&f32_to_f16(sample).to_ne_bytes());
+            }
+        }
+        SampleInterpretation::FP32 => {
+            for (sample, out) in samples.zip(out.chunks_exact_mut(4)) {
+                out.copy_from_slice(&sample.to_ne_bytes());
+            }
+        }
+    }
+}
+
+// only needs to handle values in 0..=1, everything below the smallest normal
+// half float is flushed to zero
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    if exponent <= 0 {
+        0
+    } else {
+        ((exponent as u32) << 10 | (bits >> 13) & 0x3ff) as u16
+    }
+}
+
+fn split_mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[async_trait]
+impl ProcessingNode for TestPatternSource {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
+        let frame_number = request.frame_number();
+        let mut buffer =
+            unsafe { self.context.get_uninit_cpu_buffer(self.interpretation.required_bytes()) };
+        buffer.as_mut_slice(|buffer| {
+            if self.pattern.is_static() {
+                let mut static_frame = self.static_frame.lock();
+                let static_frame = static_frame.get_or_insert_with(|| {
+                    let mut frame = vec![0; buffer.len()];
+                    self.render(frame_number, &mut frame);
+                    frame
+                });
+                buffer.copy_from_slice(static_frame);
+            } else {
+                self.render(frame_number, buffer);
+            }
+        });
+
+        Ok(Payload::from(Frame {
+            storage: buffer,
+            interpretation: self.interpretation,
+            metadata: FrameMetadata::new(),
+        }))
+    }
+    fn get_caps(&self) -> Caps { Caps { frame_count: None, random_access: true } }
+    fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::frame(self.interpretation)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestPatternSource;
+    use crate::pipeline_processing::{
+        parametrizable::prelude::*,
+        processing_context::ProcessingContext,
+        processing_graph::{ProcessingNodeConfig, SerdeNodeConfig},
+    };
+
+    fn render(yaml: &str, frame_number: u64) -> Vec<u8> {
+        let config: ProcessingNodeConfig<String> =
+            serde_yaml::from_str::<SerdeNodeConfig>(yaml).unwrap().into();
+        let parameters =
+            config.parameters.add_defaults(TestPatternSource::describe_parameters()).unwrap();
+        let source =
+            TestPatternSource::from_parameters(parameters, &[], &ProcessingContext::default())
+                .unwrap();
+        let mut frame = vec![0; source.interpretation.required_bytes()];
+        source.render(frame_number, &mut frame);
+        frame
+    }
+
+    #[test]
+    fn test_bars_packed_bayer() {
+        // two pixels per bar, red and green alternate in the first row
+        let frame = render("{type: TestPatternSource, width: 16, height: 2, uint-bits: 12}", 0);
+        assert_eq!(frame.len(), 16 * 2 * 12 / 8);
+        // white, yellow and then cyan, which has no red
+        assert_eq!(frame[..9], [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x0f, 0xff]);
+    }
+
+    #[test]
+    fn test_deterministic_patterns() {
+        let counter = "{type: TestPatternSource, pattern: counter, square-size: 2, width: 32, \
+                       height: 8, rgb: true, uint-bits: 8}";
+        assert_eq!(render(counter, 5), render(counter, 5));
+        assert_ne!(render(counter, 5), render(counter, 6));
+
+        let noise = |seed: u64| {
+            render(
+                &format!(
+                    "{{type: TestPatternSource, pattern: noise, seed: {seed}, width: 8, \
+                     height: 8, uint-bits: 16}}"
+                ),
+                0,
+            )
+        };
+        assert_eq!(noise(1), noise(1));
+        assert_ne!(noise(1), noise(2));
+    }
+}
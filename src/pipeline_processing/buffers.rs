@@ -1,12 +1,14 @@
 use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
 use owning_ref::OwningHandle;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::{
-    mem::MaybeUninit,
+    collections::HashMap,
+    mem::{ManuallyDrop, MaybeUninit},
     ops::{Deref, DerefMut},
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
+        Weak,
     },
 };
 
@@ -24,12 +26,22 @@ use vulkano::{
 
 static DROP_ID: AtomicUsize = AtomicUsize::new(0);
 
+type OnDrop<T> = Box<dyn FnOnce(T) + Send + Sync>;
+
 pub struct TrackDrop<T> {
-    val: T,
+    val: ManuallyDrop<T>,
+    // gets the value instead of dropping it, used to return buffers to the pool
+    on_drop: Option<OnDrop<T>>,
     #[cfg(feature = "track-drop")]
     id: usize,
 }
 
+impl<T> TrackDrop<T> {
+    pub fn on_drop(self, on_drop: impl FnOnce(T) + Send + Sync + 'static) -> Self {
+        Self { on_drop: Some(Box::new(on_drop)), ..self }
+    }
+}
+
 impl<T> Deref for TrackDrop<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target { &self.val }
@@ -52,7 +64,13 @@ impl<T> DerefMut for TrackDrop<T> {
 impl<T> Drop for TrackDrop<T> {
     fn drop(&mut self) {
         #[cfg(feature = "track-drop")]
-        println!("dropping {} from {:?}", self.id, backtrace::Backtrace::new())
+        println!("dropping {} from {:?}", self.id, backtrace::Backtrace::new());
+
+        // Safety: val is not accessed anymore after this
+        let val = unsafe { ManuallyDrop::take(&mut self.val) };
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop(val)
+        }
     }
 }
 
@@ -64,6 +82,10 @@ impl InfoForTrackDrop for CpuAccessibleBuffer<[u8]> {
     fn info(&self) -> String { format!("len = {}", self.len()) }
 }
 
+impl InfoForTrackDrop for Vec<u8> {
+    fn info(&self) -> String { format!("len = {}", self.len()) }
+}
+
 impl<T: InfoForTrackDrop> From<T> for TrackDrop<T> {
     fn from(val: T) -> Self {
         #[allow(unused)]
@@ -71,49 +93,57 @@ impl<T: InfoForTrackDrop> From<T> for TrackDrop<T> {
         #[cfg(feature = "track-drop")]
         eprintln!("creating {id}: {}", val.info());
         Self {
-            val,
+            val: ManuallyDrop::new(val),
+            on_drop: None,
             #[cfg(feature = "track-drop")]
             id,
         }
     }
 }
 
+/// A buffer in memory the cpu can access. Vulkan buffers can be larger than
+/// the buffer, only the first `len` bytes belong to it.
 #[derive(Clone)]
 pub enum CpuBuffer {
-    Vulkan(Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>>),
-    Vec(Arc<RwLock<Vec<u8>>>),
+    Vulkan(Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>>, usize),
+    Vec(Arc<RwLock<TrackDrop<Vec<u8>>>>),
 }
 
 impl From<Arc<CpuAccessibleBuffer<[u8]>>> for CpuBuffer {
     fn from(buf: Arc<CpuAccessibleBuffer<[u8]>>) -> Self {
-        Self::Vulkan(Arc::new(Arc::try_unwrap(buf).unwrap().into()))
+        Arc::new(Arc::try_unwrap(buf).unwrap().into()).into()
     }
 }
 
 impl From<Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>>> for CpuBuffer {
-    fn from(buf: Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>>) -> Self { Self::Vulkan(buf) }
+    fn from(buf: Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>>) -> Self {
+        let len = buf.len() as _;
+        Self::Vulkan(buf, len)
+    }
 }
 
 impl CpuBuffer {
     pub fn len(&self) -> usize {
         match self {
-            CpuBuffer::Vulkan(buf) => buf.len() as _,
+            CpuBuffer::Vulkan(_, len) => *len,
             CpuBuffer::Vec(buf) => buf.read().len(),
         }
     }
 
     pub fn is_empty(&self) -> bool { self.len() == 0 }
 
+    /// The whole vulkan buffer, it can be larger than `len`. Copies between
+    /// buffers only copy the size of the smaller one.
     pub fn cpu_accessible_buffer(&self) -> Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>> {
         match self {
-            CpuBuffer::Vulkan(buf) => buf.clone(),
+            CpuBuffer::Vulkan(buf, _) => buf.clone(),
             CpuBuffer::Vec(_) => unimplemented!(),
         }
     }
 
     pub fn as_slice<FN: FnOnce(&[u8]) -> R, R>(&self, func: FN) -> R {
         match self {
-            CpuBuffer::Vulkan(buf) => func(&*buf.read().unwrap()),
+            CpuBuffer::Vulkan(buf, len) => func(&buf.read().unwrap()[..*len]),
             CpuBuffer::Vec(buf) => func(&*buf.read()),
         }
     }
@@ -123,14 +153,14 @@ impl CpuBuffer {
         func: FN,
     ) -> R {
         match self {
-            CpuBuffer::Vulkan(buf) => func(&*buf.read().unwrap()).await,
+            CpuBuffer::Vulkan(buf, len) => func(&buf.read().unwrap()[..*len]).await,
             CpuBuffer::Vec(buf) => func(&*buf.read()).await,
         }
     }
 
     pub fn as_mut_slice<FN: FnOnce(&mut [u8]) -> R, R>(&mut self, func: FN) -> R {
         match self {
-            CpuBuffer::Vulkan(buf) => func(&mut *buf.write().unwrap()),
+            CpuBuffer::Vulkan(buf, len) => func(&mut buf.write().unwrap()[..*len]),
             CpuBuffer::Vec(buf) => func(&mut *buf.write()),
         }
     }
@@ -140,12 +170,122 @@ impl CpuBuffer {
         func: FN,
     ) -> R {
         match self {
-            CpuBuffer::Vulkan(buf) => func(&mut *buf.write().unwrap()).await,
+            CpuBuffer::Vulkan(buf, len) => func(&mut buf.write().unwrap()[..*len]).await,
             CpuBuffer::Vec(buf) => func(&mut *buf.write()).await,
         }
     }
 }
 
+/// The storage of a `CpuBuffer` while it is not used by any frame.
+pub enum PooledBuffer {
+    Vulkan(CpuAccessibleBuffer<[u8]>),
+    Vec(Vec<u8>),
+}
+
+impl PooledBuffer {
+    /// The size of the storage, not of the buffer that used it last.
+    fn size(&self) -> usize {
+        match self {
+            PooledBuffer::Vulkan(buf) => buf.len() as _,
+            PooledBuffer::Vec(buf) => buf.capacity(),
+        }
+    }
+}
+
+/// The size of the storage that is allocated for buffers of `len` bytes.
+fn size_class(len: usize) -> usize { len.next_power_of_two() }
+
+/// Keeps the storage of dropped `CpuBuffer`s around to reuse it for new
+/// buffers. The storage is allocated in power of two sizes, so that buffers of
+/// similar sizes share it. The buffers return to the pool when the last
+/// reference to them is dropped. At most `max_bytes` are kept in the pool and
+/// at most a quarter of that of a single size, so that the storage of a size
+/// that isn't used anymore doesn't take up the whole pool. Buffers that don't
+/// fit anymore are freed.
+pub struct BufferPool {
+    free: Mutex<HashMap<usize, Vec<PooledBuffer>>>,
+    pooled_bytes: AtomicUsize,
+    max_bytes: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BufferPool {
+    pub fn new(max_bytes: usize) -> Arc<Self> {
+        Arc::new(Self {
+            free: Default::default(),
+            pooled_bytes: AtomicUsize::new(0),
+            max_bytes,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns a buffer of `len` bytes from the pool or one created by
+    /// `allocate` if there is none. `allocate` gets the size of the storage to
+    /// allocate, which can be larger than `len`. The contents of the buffer
+    /// are undefined.
+    pub fn get(
+        self: &Arc<Self>,
+        len: usize,
+        allocate: impl FnOnce(usize) -> PooledBuffer,
+    ) -> CpuBuffer {
+        let size = size_class(len);
+        let pooled = self.free.lock().get_mut(&size).and_then(Vec::pop);
+        let buffer = match pooled {
+            Some(buffer) => {
+                self.pooled_bytes.fetch_sub(size, Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                allocate(size)
+            }
+        };
+
+        let pool = Arc::downgrade(self);
+        match buffer {
+            PooledBuffer::Vulkan(buf) => CpuBuffer::Vulkan(
+                Arc::new(
+                    TrackDrop::from(buf)
+                        .on_drop(move |buf| Self::put(&pool, PooledBuffer::Vulkan(buf))),
+                ),
+                len,
+            ),
+            PooledBuffer::Vec(mut buf) => {
+                assert!(buf.capacity() >= len, "allocated less than {size} bytes");
+                // Safety: the contents of the buffer are undefined, see above
+                unsafe { buf.set_len(len) };
+                CpuBuffer::Vec(Arc::new(RwLock::new(
+                    TrackDrop::from(buf)
+                        .on_drop(move |buf| Self::put(&pool, PooledBuffer::Vec(buf))),
+                )))
+            }
+        }
+    }
+
+    fn put(pool: &Weak<Self>, buffer: PooledBuffer) {
+        if let Some(pool) = pool.upgrade() {
+            let size = buffer.size();
+            let mut free = pool.free.lock();
+            let class = free.entry(size).or_default();
+            let fits_class = (class.len() + 1) * size <= pool.max_bytes / 4;
+            if fits_class && pool.pooled_bytes.load(Ordering::Relaxed) + size <= pool.max_bytes {
+                pool.pooled_bytes.fetch_add(size, Ordering::Relaxed);
+                class.push(buffer);
+            }
+        }
+    }
+
+    /// The number of requested buffers that were taken from the pool.
+    pub fn hits(&self) -> u64 { self.hits.load(Ordering::Relaxed) }
+    /// The number of requested buffers that had to be allocated.
+    pub fn misses(&self) -> u64 { self.misses.load(Ordering::Relaxed) }
+    /// The size of the buffers currently held by the pool.
+    pub fn pooled_bytes(&self) -> usize { self.pooled_bytes.load(Ordering::Relaxed) }
+}
+
 type BufHolder<'a> = OwningHandle<
     Arc<TrackDrop<CpuAccessibleBuffer<[u8]>>>,
     WriteLock<'a, [u8], PotentialDedicatedAllocation<StdMemoryPoolAlloc>>,
@@ -156,6 +296,7 @@ pub struct ChunkedCpuBuffer<'a, Extra, const N: usize> {
     locks: Vec<futures::lock::Mutex<(usize, Extra)>>,
     n: usize,
     chunk_sizes: [usize; N],
+    lens: [usize; N],
     ptrs: [*mut u8; N],
 }
 
@@ -167,15 +308,17 @@ where
     Extra: Default,
 {
     pub fn new(cpu_buffers: [CpuBuffer; N], n: usize) -> Self {
+        let mut lens = [0; N];
         let (buf_holders, ptrs, chunk_sizes) = unsafe {
             let mut buf_holders: [MaybeUninit<BufHolder<'_>>; N] =
                 MaybeUninit::uninit().assume_init();
             let mut ptrs = [std::ptr::null_mut::<u8>(); N];
             let mut chunk_sizes = [0; N];
             for (i, cpu_buffer) in cpu_buffers.into_iter().enumerate() {
+                lens[i] = cpu_buffer.len();
                 chunk_sizes[i] = cpu_buffer.len() / n;
 
-                let buf = if let CpuBuffer::Vulkan(buf) = cpu_buffer {
+                let buf = if let CpuBuffer::Vulkan(buf, _) = cpu_buffer {
                     buf.clone()
                 } else {
                     // TODO: Implement for CpuBuffer::Vec
@@ -196,7 +339,7 @@ where
             .map(|i| futures::lock::Mutex::new((i, Default::default())))
             .collect();
 
-        Self { buf_holders, n, chunk_sizes, locks, ptrs }
+        Self { buf_holders, n, chunk_sizes, lens, locks, ptrs }
     }
 
     pub async fn zip_with<O, F: for<'b> Fn([&'b mut [u8]; N], &'b [O], &'b mut Extra) + Clone>(
@@ -226,7 +369,8 @@ where
     }
 
     pub fn unchunk(self) -> [CpuBuffer; N] {
-        self.buf_holders.map(|holder| holder.into_owner().into())
+        let mut lens = self.lens.into_iter();
+        self.buf_holders.map(|holder| CpuBuffer::Vulkan(holder.into_owner(), lens.next().unwrap()))
     }
 }
 
@@ -252,3 +396,42 @@ impl GpuBuffer {
     }
     pub fn untyped(&self) -> Arc<(dyn BufferAccess)> { self.buffer_access.clone() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferPool, PooledBuffer};
+
+    #[test]
+    fn test_buffer_pool_reuse() {
+        let pool = BufferPool::new(256);
+        let allocate = |size| PooledBuffer::Vec(vec![0; size]);
+
+        // the storage is allocated in the size class of 32 bytes
+        let buffer = pool.get(24, allocate);
+        assert_eq!(buffer.len(), 24);
+        drop(buffer);
+        assert_eq!(pool.pooled_bytes(), 32);
+
+        // buffers of similar sizes share the storage
+        let first = pool.get(30, allocate);
+        let second = pool.get(32, allocate);
+        assert_eq!((first.len(), second.len()), (30, 32));
+        assert_eq!((pool.hits(), pool.misses()), (1, 2));
+        assert_eq!(pool.pooled_bytes(), 0);
+
+        // the buffer is only returned once the last reference is dropped
+        let clone = first.clone();
+        drop(first);
+        assert_eq!(pool.pooled_bytes(), 0);
+        drop(clone);
+        drop(second);
+        assert_eq!(pool.pooled_bytes(), 64);
+
+        // a single size class only takes up a quarter of the pool, the third
+        // buffer is freed
+        let buffers = (0..3).map(|_| pool.get(32, allocate)).collect::<Vec<_>>();
+        assert_eq!((pool.hits(), pool.misses()), (3, 3));
+        drop(buffers);
+        assert_eq!(pool.pooled_bytes(), 64);
+    }
+}
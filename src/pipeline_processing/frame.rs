@@ -1,3 +1,6 @@
+use anymap::CloneAny;
+use std::{path::PathBuf, time::Duration};
+
 pub trait ToAny: 'static {
     fn as_any(&self) -> &dyn std::any::Any;
 }
@@ -10,6 +13,70 @@ impl<T: 'static> ToAny for T {
 pub struct Frame<Storage> {
     pub interpretation: FrameInterpretation,
     pub storage: Storage,
+    pub metadata: FrameMetadata,
+}
+
+/// Extensible per frame information like capture timestamps or the file a frame
+/// was read from. Nodes that derive a frame from an input frame should pass the
+/// metadata on.
+pub type FrameMetadata = anymap::Map<dyn CloneAny + Send + Sync>;
+
+// types that are common to end up in the FrameMetadata:
+
+/// The path of the file the frame was read from.
+#[derive(Clone, Debug)]
+pub struct SourceFilename(pub PathBuf);
+
+/// The time the frame was captured at, relative to a source specific epoch.
+#[derive(Copy, Clone, Debug)]
+pub struct CaptureTimestamp(pub Duration);
+
+/// A non drop frame SMPTE timecode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+}
+impl Timecode {
+    /// The timecode of the frame with the given number, counting from
+    /// 00:00:00:00.
+    pub fn from_frame_number(frame_number: u64, fps: f64) -> Self {
+        let fps = (fps.round() as u64).max(1);
+        let seconds = frame_number / fps;
+        Self {
+            hours: (seconds / 3600 % 24) as u8,
+            minutes: (seconds / 60 % 60) as u8,
+            seconds: (seconds % 60) as u8,
+            frames: (frame_number % fps) as u8,
+        }
+    }
+
+    /// The BCD encoding of SMPTE 12M as used by the `TimeCodes` tag of
+    /// CinemaDNG. The user bits are left empty.
+    pub fn to_smpte_bytes(self) -> [u8; 8] {
+        let bcd = |v: u8| ((v / 10) << 4) | (v % 10);
+        [bcd(self.frames), bcd(self.seconds), bcd(self.minutes), bcd(self.hours), 0, 0, 0, 0]
+    }
+
+    pub fn from_smpte_bytes(bytes: &[u8]) -> Option<Self> {
+        let from_bcd = |v: u8, mask: u8| ((v & mask) >> 4) * 10 + (v & mask & 0xf);
+        match bytes {
+            [frames, seconds, minutes, hours, ..] => Some(Self {
+                hours: from_bcd(*hours, 0x3f),
+                minutes: from_bcd(*minutes, 0x7f),
+                seconds: from_bcd(*seconds, 0x7f),
+                frames: from_bcd(*frames, 0x3f),
+            }),
+            _ => None,
+        }
+    }
+}
+impl std::fmt::Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}:{:02}", self.hours, self.minutes, self.seconds, self.frames)
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -38,6 +105,24 @@ impl FrameInterpretation {
     }
 }
 
+impl std::fmt::Display for FrameInterpretation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} {} ", self.width, self.height, self.color_interpretation)?;
+        match self.sample_interpretation {
+            SampleInterpretation::UInt(bits) => write!(f, "uint{bits}")?,
+            SampleInterpretation::FP16 => write!(f, "fp16")?,
+            SampleInterpretation::FP32 => write!(f, "fp32")?,
+        }
+        if let Compression::SZ3Compressed { .. } = self.compression {
+            write!(f, " sz3")?;
+        }
+        if let Some(fps) = self.fps {
+            write!(f, " @ {fps}fps")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum ColorInterpretation {
     Bayer(CfaDescriptor),
@@ -54,6 +139,24 @@ impl ColorInterpretation {
     }
 }
 
+impl std::fmt::Display for ColorInterpretation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorInterpretation::Bayer(cfa) => {
+                let pattern = match (cfa.red_in_first_row, cfa.red_in_first_col) {
+                    (true, true) => "RGGB",
+                    (true, false) => "GRBG",
+                    (false, true) => "GBRG",
+                    (false, false) => "BGGR",
+                };
+                write!(f, "bayer {pattern}")
+            }
+            ColorInterpretation::Rgb => write!(f, "rgb"),
+            ColorInterpretation::Rgba => write!(f, "rgba"),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct CfaDescriptor {
     pub red_in_first_col: bool,
@@ -86,3 +189,22 @@ pub enum Compression {
     Uncompressed,
     SZ3Compressed { size: usize },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Timecode;
+
+    #[test]
+    fn test_timecode() {
+        let timecode = Timecode::from_frame_number(90061 * 24 + 5, 23.976);
+        assert_eq!(timecode, Timecode { hours: 1, minutes: 1, seconds: 1, frames: 5 });
+        assert_eq!(timecode.to_string(), "01:01:01:05");
+
+        let bytes = Timecode { hours: 23, minutes: 59, seconds: 42, frames: 17 }.to_smpte_bytes();
+        assert_eq!(bytes[..4], [0x17, 0x42, 0x59, 0x23]);
+        assert_eq!(
+            Timecode::from_smpte_bytes(&bytes),
+            Some(Timecode { hours: 23, minutes: 59, seconds: 42, frames: 17 })
+        );
+    }
+}
@@ -57,7 +57,14 @@ pub fn to_immutable_buffer(
         (buffer, future)
     };
 
-    (Frame { interpretation: frame.interpretation.clone(), storage: buffer.into() }, fut)
+    (
+        Frame {
+            interpretation: frame.interpretation.clone(),
+            storage: buffer.into(),
+            metadata: frame.metadata.clone(),
+        },
+        fut,
+    )
 }
 
 pub fn ensure_gpu_buffer_frame(
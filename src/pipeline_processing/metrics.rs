@@ -0,0 +1,297 @@
+use crate::pipeline_processing::{
+    buffers::{CpuBuffer, GpuBuffer},
+    frame::Frame,
+    node::NodeID,
+    payload::Payload,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+const LATENCY_BUCKETS: usize = 32;
+
+/// A histogram of durations with power of two buckets (in microseconds).
+/// Bucket `i` counts the durations in `[2^(i-1), 2^i)` microseconds.
+#[derive(Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect()
+    }
+
+    /// Returns the upper bound of the bucket containing the `quantile` (in
+    /// `0.0..=1.0`) of the recorded durations or `None` if nothing was
+    /// recorded yet.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let counts = self.counts();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let target = ((total as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some(Duration::from_micros(1 << bucket));
+            }
+        }
+        unreachable!()
+    }
+}
+
+/// Accumulates the wall time during which at least one of possibly
+/// overlapping intervals is open.
+#[derive(Default)]
+struct OverlapTimer {
+    state: Mutex<OverlapState>,
+}
+
+#[derive(Default)]
+struct OverlapState {
+    open: usize,
+    since: Option<Instant>,
+    total: Duration,
+}
+
+impl OverlapTimer {
+    fn open(&self, now: Instant) {
+        let mut state = self.state.lock();
+        if state.open == 0 {
+            state.since = Some(now);
+        }
+        state.open += 1;
+    }
+
+    fn close(&self, now: Instant) {
+        let mut state = self.state.lock();
+        state.open -= 1;
+        if state.open == 0 {
+            let since = state.since.take().unwrap();
+            state.total += now.saturating_duration_since(since);
+        }
+    }
+
+    fn total(&self, now: Instant) -> Duration {
+        let state = self.state.lock();
+        match state.since {
+            Some(since) => state.total + now.saturating_duration_since(since),
+            None => state.total,
+        }
+    }
+}
+
+/// Runtime statistics of a single node. They are recorded by
+/// `InputProcessingNode::pull`, so every pull of the node by one of its
+/// consumers is counted. The times are wall times, pulls that overlap count
+/// only once.
+#[derive(Default)]
+pub struct NodeMetrics {
+    pub name: String,
+    pub node_type: String,
+    pulls: AtomicU64,
+    errors: AtomicU64,
+    bytes: AtomicU64,
+    busy: OverlapTimer,
+    waiting: OverlapTimer,
+    pub latency: LatencyHistogram,
+}
+
+impl NodeMetrics {
+    pub fn pulls(&self) -> u64 { self.pulls.load(Ordering::Relaxed) }
+    pub fn errors(&self) -> u64 { self.errors.load(Ordering::Relaxed) }
+    /// The size of all frames produced by this node.
+    pub fn bytes(&self) -> u64 { self.bytes.load(Ordering::Relaxed) }
+    /// The wall time during which at least one pull of this node was in
+    /// progress, including the time it waited for its inputs.
+    pub fn busy_time(&self) -> Duration { self.busy.total(Instant::now()) }
+    /// The wall time during which this node waited for at least one pull of
+    /// its inputs. Inputs that are pulled concurrently count only once.
+    pub fn waiting_time(&self) -> Duration { self.waiting.total(Instant::now()) }
+    /// The time this node spent on its own work.
+    pub fn work_time(&self) -> Duration { self.busy_time().saturating_sub(self.waiting_time()) }
+}
+
+/// The metrics of all nodes of a `ProcessingGraph`, indexed by `NodeID`.
+pub struct GraphMetrics {
+    nodes: Vec<NodeMetrics>,
+}
+
+impl GraphMetrics {
+    pub(crate) fn new(nodes: Vec<NodeMetrics>) -> Self { Self { nodes } }
+
+    pub fn get(&self, id: NodeID) -> Option<&NodeMetrics> { self.nodes.get(usize::from(id)) }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeID, &NodeMetrics)> {
+        self.nodes.iter().enumerate().map(|(idx, metrics)| (idx.into(), metrics))
+    }
+
+    /// Starts a pull of `source`, it ends when the returned guard is dropped,
+    /// also if the pull is cancelled.
+    pub(crate) fn start_pull(&self, source: NodeID) -> Option<IntervalGuard<'_>> {
+        Some(IntervalGuard::open(&self.get(source)?.busy))
+    }
+
+    /// Starts a wait of `puller` for one of its inputs, like `start_pull`.
+    pub(crate) fn start_wait(&self, puller: NodeID) -> Option<IntervalGuard<'_>> {
+        Some(IntervalGuard::open(&self.get(puller)?.waiting))
+    }
+
+    pub(crate) fn record_pull(&self, source: NodeID, duration: Duration, result: &Result<Payload>) {
+        if let Some(metrics) = self.get(source) {
+            metrics.pulls.fetch_add(1, Ordering::Relaxed);
+            metrics.latency.record(duration);
+            match result {
+                Ok(payload) => {
+                    metrics.bytes.fetch_add(payload_bytes(payload), Ordering::Relaxed);
+                }
+                Err(_) => {
+                    metrics.errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Renders a table with one line per node.
+    pub fn report(&self) -> String {
+        let format_latency =
+            |latency: Option<Duration>| latency.map_or("-".to_string(), |l| format!("{l:?}"));
+
+        let mut report = format!(
+            "{:<24} {:<24} {:>8} {:>6} {:>10} {:>10} {:>10} {:>10} {:>10}\n",
+            "node", "type", "pulls", "errors", "p50", "p99", "work", "waiting", "MiB"
+        );
+        for (_, metrics) in self.iter() {
+            writeln!(
+                report,
+                "{:<24} {:<24} {:>8} {:>6} {:>10} {:>10} {:>10.2?} {:>10.2?} {:>10.1}",
+                metrics.name,
+                metrics.node_type,
+                metrics.pulls(),
+                metrics.errors(),
+                format_latency(metrics.latency.quantile(0.5)),
+                format_latency(metrics.latency.quantile(0.99)),
+                metrics.work_time(),
+                metrics.waiting_time(),
+                metrics.bytes() as f64 / (1024.0 * 1024.0),
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+pub(crate) struct IntervalGuard<'a> {
+    timer: &'a OverlapTimer,
+}
+
+impl<'a> IntervalGuard<'a> {
+    fn open(timer: &'a OverlapTimer) -> Self {
+        timer.open(Instant::now());
+        Self { timer }
+    }
+}
+
+impl Drop for IntervalGuard<'_> {
+    fn drop(&mut self) { self.timer.close(Instant::now()) }
+}
+
+pub(crate) fn payload_bytes(payload: &Payload) -> u64 {
+    if let Some(frame) = payload.downcast_ref::<Frame<CpuBuffer>>() {
+        frame.storage.len() as u64
+    } else if let Some(frame) = payload.downcast_ref::<Frame<GpuBuffer>>() {
+        frame.interpretation.required_bytes() as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GraphMetrics, LatencyHistogram, NodeMetrics, OverlapTimer};
+    use crate::pipeline_processing::{node::NodeID, payload::Payload};
+    use anyhow::anyhow;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_latency_quantiles() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+
+        for _ in 0..98 {
+            histogram.record(Duration::from_micros(100));
+        }
+        histogram.record(Duration::from_millis(10));
+        histogram.record(Duration::from_secs(1));
+
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_micros(128)));
+        assert_eq!(histogram.quantile(0.99), Some(Duration::from_micros(16384)));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_micros(1 << 20)));
+    }
+
+    #[test]
+    fn test_record_pull() {
+        let metrics = GraphMetrics::new(vec![NodeMetrics::default(), NodeMetrics::default()]);
+        let source = NodeID::from(0);
+
+        metrics.record_pull(source, Duration::from_millis(3), &Ok(Payload::empty()));
+        metrics.record_pull(source, Duration::from_millis(1), &Err(anyhow!("failed")));
+
+        let source = metrics.get(source).unwrap();
+        assert_eq!(source.pulls(), 2);
+        assert_eq!(source.errors(), 1);
+        assert_eq!(source.latency.counts().iter().sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn test_overlapping_waits() {
+        let timer = OverlapTimer::default();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        // two inputs pulled concurrently
+        timer.open(at(0));
+        timer.open(at(1));
+        timer.close(at(3));
+        timer.close(at(4));
+        assert_eq!(timer.total(at(10)), Duration::from_millis(4));
+
+        // a wait in progress counts until now
+        timer.open(at(10));
+        assert_eq!(timer.total(at(12)), Duration::from_millis(6));
+    }
+
+    #[test]
+    fn test_work_time_of_overlapping_pulls() {
+        let metrics = NodeMetrics::default();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        // two pulls of the node, each waits for its input for 2ms
+        metrics.busy.open(at(0));
+        metrics.waiting.open(at(0));
+        metrics.busy.open(at(1));
+        metrics.waiting.open(at(1));
+        metrics.waiting.close(at(2));
+        metrics.waiting.close(at(3));
+        metrics.busy.close(at(4));
+        metrics.busy.close(at(5));
+
+        assert_eq!(metrics.busy_time(), Duration::from_millis(5));
+        assert_eq!(metrics.waiting_time(), Duration::from_millis(3));
+        assert_eq!(metrics.work_time(), Duration::from_millis(2));
+    }
+}
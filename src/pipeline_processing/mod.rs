@@ -1,6 +1,7 @@
 pub mod buffers;
 pub mod frame;
 pub mod gpu_util;
+pub mod metrics;
 pub mod node;
 pub mod parametrizable;
 pub mod payload;
@@ -8,3 +9,6 @@ pub mod prioritized_executor;
 pub mod processing_context;
 pub mod processing_graph;
 pub mod puller;
+pub mod schema;
+pub mod sink_control;
+pub mod subgraph;
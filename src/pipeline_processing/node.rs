@@ -1,20 +1,32 @@
-use crate::pipeline_processing::{
-    payload::Payload,
-    processing_context::{Priority, ProcessingContext},
+use crate::{
+    pipeline_processing::{
+        metrics::GraphMetrics,
+        parametrizable::Parameters,
+        payload::{Payload, PayloadType},
+        processing_context::{Priority, ProcessingContext},
+    },
+    util::lru::LruStats,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use anymap::CloneAny;
 use async_trait::async_trait;
 
 use std::{
     fmt::{Debug, Formatter},
     sync::Arc,
+    time::Instant,
 };
 
 #[derive(thiserror::Error, Debug)]
 #[error("end of file")]
 pub struct EOFError;
 
+/// Returned instead of a payload when a request carrying the `Drop` hint was
+/// not fulfilled.
+#[derive(thiserror::Error, Debug)]
+#[error("frame was dropped")]
+pub struct DroppedError;
+
 #[derive(Clone, Copy, Default, Debug)]
 pub struct Caps {
     pub frame_count: Option<u64>,
@@ -56,6 +68,25 @@ impl Request {
     {
         self.extra.get::<T>()
     }
+    pub fn with_extra<T>(&self, extra: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let mut request = self.clone();
+        request.extra.insert(extra);
+        request
+    }
+    pub fn without_extra<T>(&self) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let mut request = self.clone();
+        request.extra.remove::<T>();
+        request
+    }
+    /// Whether the requester is fine with getting a `DroppedError` instead of
+    /// the payload (see `Drop`).
+    pub fn is_droppable(&self) -> bool { self.get_extra::<Drop>().is_some() }
 }
 
 // types that are common to end up in the extra AnyMap of Request:
@@ -63,7 +94,7 @@ impl Request {
 /// Indicates that the frame request does not really need to be fulfilled but it
 /// is okay to omit all the work and instead return an error. A processingBlock
 /// _must_ pull all its inputs as it would usually do and indecate the FrameDrop
-/// to them.
+/// to them. Nodes that skip their work return a `DroppedError`.
 #[derive(Copy, Clone, Debug)]
 pub struct Drop;
 
@@ -77,6 +108,29 @@ pub struct PinCache;
 pub trait ProcessingNode {
     async fn pull(&self, request: Request) -> Result<Payload>;
     fn get_caps(&self) -> Caps;
+    /// Statically derives the type of the produced payloads from the types of
+    /// the inputs. Returns an error if the inputs are incompatible with this
+    /// node.
+    fn get_output_type(&self) -> Result<PayloadType>;
+    /// Takes new values for the parameters that were declared with
+    /// `ParametersDescriptor::with_live`. The values are already parsed and
+    /// checked against the descriptor, they have to be used from the next
+    /// pulled frame on.
+    fn update_parameters(&self, _parameters: Parameters) -> Result<()> {
+        bail!("this node has no live parameters")
+    }
+    /// One of the named outputs after the first of `Parameterizable::OUTPUTS`
+    /// (the first one is the node itself). Every output has its own caps and
+    /// output type.
+    fn output_port(&self, name: &str) -> Result<Arc<dyn ProcessingNode + Send + Sync>> {
+        bail!("this node has no output {name}")
+    }
+    /// Whether the node keeps every frame until all of its consumers pulled it,
+    /// like `Cache`. The sinks behind such a node can't be disarmed, the frames
+    /// they skip would never be released.
+    fn holds_frames_for_consumers(&self) -> bool { false }
+    /// The statistics of the frame cache of the node, if it has one.
+    fn cache_stats(&self) -> Option<LruStats> { None }
 }
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Default)]
@@ -95,22 +149,40 @@ impl From<usize> for NodeID {
 pub struct InputProcessingNode {
     node: Arc<dyn ProcessingNode + Send + Sync>,
     node_id: NodeID,
+    // the id of the wrapped node and where to record its pulls
+    metrics: Option<(NodeID, Arc<GraphMetrics>)>,
 }
 
 impl InputProcessingNode {
     pub(crate) fn new(puller_id: NodeID, node: Arc<dyn ProcessingNode + Send + Sync>) -> Self {
-        Self { node, node_id: puller_id }
+        Self { node, node_id: puller_id, metrics: None }
+    }
+
+    pub(crate) fn with_metrics(self, node_id: NodeID, metrics: Arc<GraphMetrics>) -> Self {
+        Self { metrics: Some((node_id, metrics)), ..self }
     }
 
     pub async fn pull(&self, request: Request) -> Result<Payload> {
-        self.node.pull(request.with_requester(self.node_id)).await
+        let start = Instant::now();
+        let _busy =
+            self.metrics.as_ref().and_then(|(source_id, metrics)| metrics.start_pull(*source_id));
+        let _wait = self.metrics.as_ref().and_then(|(_, metrics)| metrics.start_wait(self.node_id));
+        let result = self.node.pull(request.with_requester(self.node_id)).await;
+        if let Some((source_id, metrics)) = &self.metrics {
+            metrics.record_pull(*source_id, start.elapsed(), &result);
+        }
+        result
     }
 
-    fn copy_with(&self, node_id: NodeID) -> Self { Self { node: self.node.clone(), node_id } }
+    fn copy_with(&self, node_id: NodeID) -> Self {
+        Self { node: self.node.clone(), node_id, metrics: self.metrics.clone() }
+    }
 
     pub(crate) fn clone_for_same_puller(&self) -> Self { self.copy_with(self.node_id) }
 
     pub fn get_caps(&self) -> Caps { self.node.get_caps() }
+
+    pub fn get_output_type(&self) -> Result<PayloadType> { self.node.get_output_type() }
 }
 
 #[async_trait]
@@ -120,6 +192,17 @@ pub trait SinkNode {
         context: &ProcessingContext,
         progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
     ) -> Result<()>;
+    /// Statically checks that the types of the inputs are compatible with this
+    /// sink.
+    fn check_input_types(&self) -> Result<()>;
+    /// Called once after `run` returned (also if it failed). Writers should
+    /// flush and close their outputs here so that stopping the pipeline early
+    /// leaves valid files behind.
+    async fn finalize(&self, _context: &ProcessingContext) -> Result<()> { Ok(()) }
+    /// See `ProcessingNode::update_parameters`.
+    fn update_parameters(&self, _parameters: Parameters) -> Result<()> {
+        bail!("this node has no live parameters")
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -151,6 +234,20 @@ impl Node {
             }
         }
     }
+
+    pub fn check_types(&self) -> Result<()> {
+        match self {
+            Self::Node(node) => node.get_output_type().map(|_| ()),
+            Self::Sink(sink) => sink.check_input_types(),
+        }
+    }
+
+    pub fn update_parameters(&self, parameters: Parameters) -> Result<()> {
+        match self {
+            Self::Node(node) => node.update_parameters(parameters),
+            Self::Sink(sink) => sink.update_parameters(parameters),
+        }
+    }
 }
 impl Debug for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
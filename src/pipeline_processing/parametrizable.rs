@@ -1,21 +1,28 @@
-use crate::pipeline_processing::{
-    frame::{
-        CfaDescriptor,
-        ColorInterpretation,
-        Compression,
-        FrameInterpretation,
-        SampleInterpretation,
+use crate::{
+    pipeline_processing::{
+        frame::{
+            CfaDescriptor,
+            ColorInterpretation,
+            Compression,
+            FrameInterpretation,
+            SampleInterpretation,
+        },
+        node::{InputProcessingNode, NodeID},
+        processing_context::ProcessingContext,
+        processing_graph::{closest_match, did_you_mean},
+        puller::{ErrorPolicy, FrameCount},
     },
-    node::{InputProcessingNode, Node, NodeID},
-    processing_context::ProcessingContext,
+    util::lru::FrameLru,
 };
 use anyhow::{anyhow, bail, Context, Error, Result};
+use parking_lot::Mutex;
 use prelude::*;
 use std::{
     any::type_name,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryInto,
     fmt::{Debug, Formatter},
+    sync::Arc,
 };
 
 pub enum ParameterValue {
@@ -169,15 +176,23 @@ impl TryInto<InputProcessingNode> for ParameterValue {
 #[derive(Debug)]
 pub struct Parameters {
     values: HashMap<String, ParameterValue>,
+    // the keys the node looked at, shared so it can be inspected after
+    // `from_parameters` consumed the parameters
+    used: Arc<Mutex<HashSet<String>>>,
 }
 
 impl Parameters {
-    pub fn new(values: HashMap<String, ParameterValue>) -> Self { Self { values } }
+    pub fn new(values: HashMap<String, ParameterValue>) -> Self {
+        Self { values, used: Default::default() }
+    }
+
+    fn mark_used(&self, key: &str) { self.used.lock().insert(key.to_string()); }
 
     pub fn take<T>(&mut self, key: &str) -> Result<T>
     where
         ParameterValue: TryInto<T, Error = Error>,
     {
+        self.mark_used(key);
         let parameter_value = self
             .values
             .remove(key)
@@ -190,6 +205,7 @@ impl Parameters {
     where
         ParameterValue: TryInto<T, Error = Error>,
     {
+        self.mark_used(key);
         let parameter_value = self
             .values
             .remove(key)
@@ -206,42 +222,79 @@ impl Parameters {
     where
         ParameterValue: TryInto<T, Error = Error>,
     {
+        self.mark_used(key);
         let parameter_value = self.values.remove(key);
         parameter_value.map(|v| v.try_into()).transpose()
     }
 
-    pub fn has(&self, key: &str) -> bool { self.values.contains_key(key) }
+    pub fn has(&self, key: &str) -> bool {
+        self.mark_used(key);
+        self.values.contains_key(key)
+    }
 
-    pub(crate) fn add_inputs(
-        mut self,
-        puller_id: NodeID,
-        inputs: HashMap<String, Node>,
-    ) -> Result<Self> {
-        for (name, node) in inputs {
-            self.values.insert(
-                name.clone(),
-                ParameterValue::NodeInputValue(InputProcessingNode::new(
-                    puller_id,
-                    node.assert_input_node().with_context(|| {
-                        format!("could not convert input {name} to a input node")
-                    })?,
-                )),
-            );
-        }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &ParameterValue)> { self.values.iter() }
 
-        Ok(self)
+    pub(crate) fn clone_for_same_puller(&self) -> Self {
+        Self {
+            values: self
+                .values
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone_for_same_puller()))
+                .collect(),
+            used: Default::default(),
+        }
     }
 
-    pub(crate) fn add_defaults(mut self, description: ParametersDescriptor) -> Self {
-        for (name, value) in description.0 {
-            if let WithDefault(_, value) = value {
-                self.values.entry(name).or_insert(value);
+    /// A handle to the set of keys the node read so far, stays valid after
+    /// the parameters were consumed.
+    pub(crate) fn used_keys(&self) -> Arc<Mutex<HashSet<String>>> { self.used.clone() }
+
+    /// Inputs named like `inputs[0]` (see `list_input_name`) are collected
+    /// into a list `inputs`, ordered by their index.
+    pub(crate) fn add_inputs(mut self, inputs: HashMap<String, InputProcessingNode>) -> Self {
+        let mut lists = BTreeMap::<String, BTreeMap<usize, InputProcessingNode>>::new();
+        for (name, input) in inputs {
+            match parse_list_input_name(&name) {
+                Some((list, index)) => {
+                    lists.entry(list.to_string()).or_default().insert(index, input);
+                }
+                None => {
+                    self.values.insert(name, NodeInputValue(input));
+                }
             }
         }
+        for (name, list) in lists {
+            self.values.insert(name, ListValue(list.into_values().map(NodeInputValue).collect()));
+        }
 
         self
     }
 
+    /// Checks the given values against their declared types and fills in the
+    /// defaults of the missing ones.
+    pub(crate) fn add_defaults(mut self, description: ParametersDescriptor) -> Result<Self> {
+        for (name, descriptor) in description.0 {
+            let (ty, default) = match descriptor {
+                Mandatory(ty) | Optional(ty) => (ty, None),
+                WithDefault(ty, value) => (ty, Some(value)),
+            };
+            match (self.values.remove(&name), default) {
+                (Some(value), _) => {
+                    let value = ty
+                        .value_is_of_type(value)
+                        .with_context(|| format!("invalid value for parameter {name}"))?;
+                    self.values.insert(name, value);
+                }
+                (None, Some(default)) => {
+                    self.values.insert(name, default);
+                }
+                (None, None) => {}
+            }
+        }
+
+        Ok(self)
+    }
+
     pub fn get_interpretation(&mut self) -> Result<FrameInterpretation> {
         let width = self.take("width")?;
         let height = self.take("height")?;
@@ -259,9 +312,14 @@ impl Parameters {
             }
         };
 
+        // `bayer` has a default, so the flags have to be checked first
         let color_interpretation = {
-            if let Some(pattern) = self.take_option::<String>("bayer")? {
-                match pattern.to_uppercase().as_str() {
+            if self.take("rgb")? {
+                ColorInterpretation::Rgb
+            } else if self.take("rgba")? {
+                ColorInterpretation::Rgba
+            } else {
+                match self.take::<String>("bayer")?.to_uppercase().as_str() {
                     "RGBG" => ColorInterpretation::Bayer(CfaDescriptor {
                         red_in_first_col: true,
                         red_in_first_row: true,
@@ -280,12 +338,6 @@ impl Parameters {
                     }),
                     _ => bail!("couldn't parse CFA Pattern"),
                 }
-            } else if self.take("rgb")? {
-                ColorInterpretation::Rgb
-            } else if self.take("rgba")? {
-                ColorInterpretation::Rgba
-            } else {
-                bail!("no color interpretation was specified")
             }
         };
 
@@ -298,6 +350,35 @@ impl Parameters {
             compression: Compression::Uncompressed,
         })
     }
+
+    pub fn get_error_policy(&mut self) -> Result<ErrorPolicy> {
+        self.take::<String>("on-error")?.parse()
+    }
+
+    pub fn get_frame_cache(&mut self) -> Result<Option<FrameLru>> {
+        let max_bytes: u64 = self.take("cache-max-bytes")?;
+        Ok(self.take::<bool>("cache-frames")?.then(|| FrameLru::new(max_bytes as usize)))
+    }
+
+    pub fn get_frame_count(&mut self) -> Result<FrameCount> {
+        let number_of_frames = self.take_option("number-of-frames")?;
+        let max_consecutive_failures = self.take("max-consecutive-failures")?;
+        match (number_of_frames, self.take::<bool>("exact-number-of-frames")?) {
+            (Some(frames), true) => Ok(FrameCount::Exact { frames, max_consecutive_failures }),
+            (None, true) => bail!("exact-number-of-frames needs number-of-frames to be set"),
+            (Some(frames), false) => Ok(FrameCount::Indices(frames)),
+            (None, false) => Ok(FrameCount::All),
+        }
+    }
+}
+
+/// The name of the input that is connected as element `index` of the list of
+/// node inputs `name`.
+pub fn list_input_name(name: &str, index: usize) -> String { format!("{name}[{index}]") }
+
+fn parse_list_input_name(name: &str) -> Option<(&str, usize)> {
+    let (name, index) = name.strip_suffix(']')?.split_once('[')?;
+    Some((name, index.parse().ok()?))
 }
 
 #[derive(Debug, Clone)]
@@ -306,15 +387,43 @@ pub enum ParameterType {
     IntRange(i64, i64),
     ListParameter(Box<ParameterType>),
     StringParameter,
+    /// a string that has to be one of the given values, compared ignoring
+    /// case
+    Choice(&'static [&'static str]),
     BoolParameter,
     NodeInputParameter,
 }
 
 impl ParameterType {
+    /// Node inputs are connected in the graph instead of being given a value.
+    pub fn is_node_input(&self) -> bool {
+        match self {
+            NodeInputParameter => true,
+            ListParameter(ty) => ty.is_node_input(),
+            _ => false,
+        }
+    }
     pub fn value_is_of_type(&self, value: ParameterValue) -> Result<ParameterValue> {
+        let value = match (self, value) {
+            (ListParameter(ty), ListValue(values)) => {
+                let values = values.into_iter().map(|v| ty.value_is_of_type(v));
+                return Ok(ListValue(values.collect::<Result<_>>()?));
+            }
+            (_, value) => value,
+        };
         match (self, &value) {
             (StringParameter, StringValue(_)) => Ok(value),
+            (Choice(choices), StringValue(v)) => {
+                if choices.iter().any(|choice| choice.eq_ignore_ascii_case(v)) {
+                    Ok(value)
+                } else {
+                    Err(anyhow!("value {} is not one of {}", v, choices.join(", ")))
+                }
+            }
             (BoolParameter, BoolValue(_)) => Ok(value),
+            (NodeInputParameter, NodeInputValue(_)) => Ok(value),
+            // yaml has no way to write a whole number as float
+            (FloatRange(..), IntRangeValue(v)) => self.value_is_of_type(FloatRangeValue(*v as f64)),
             (FloatRange(min, max), FloatRangeValue(v)) => {
                 if (v >= min) && (v <= max) {
                     Ok(value)
@@ -335,6 +444,11 @@ impl ParameterType {
     pub fn parse(&self, string: &str) -> Result<ParameterValue> {
         match self {
             StringParameter => Ok(StringValue(string.to_string())),
+            Choice(choices) => choices
+                .iter()
+                .find(|choice| choice.eq_ignore_ascii_case(string))
+                .map(|choice| StringValue(choice.to_string()))
+                .ok_or_else(|| anyhow!("value {} is not one of {}", string, choices.join(", "))),
             BoolParameter => Ok(BoolValue(string.parse()?)),
             IntRange(..) => self.value_is_of_type(IntRangeValue(string.parse()?)),
             FloatRange(..) => self.value_is_of_type(FloatRangeValue(string.parse()?)),
@@ -378,19 +492,32 @@ impl ParameterTypeDescriptor {
     }
 }
 
+/// The parameters a node takes and the names of those of them that can be
+/// changed while the node is running.
 #[derive(Clone, Debug)]
-pub struct ParametersDescriptor(pub HashMap<String, ParameterTypeDescriptor>);
+pub struct ParametersDescriptor(pub HashMap<String, ParameterTypeDescriptor>, pub HashSet<String>);
 
 impl Default for ParametersDescriptor {
     fn default() -> Self { Self::new() }
 }
 
 impl ParametersDescriptor {
-    pub fn new() -> Self { ParametersDescriptor(HashMap::new()) }
+    pub fn new() -> Self { ParametersDescriptor(HashMap::new(), HashSet::new()) }
     pub fn with(mut self, name: &str, descriptor: ParameterTypeDescriptor) -> ParametersDescriptor {
         self.0.insert(name.to_string(), descriptor);
-        ParametersDescriptor(self.0)
+        self
     }
+    /// like `with`, but the parameter can also be updated while the graph is
+    /// running (see `ProcessingGraph::update_parameters`)
+    pub fn with_live(
+        mut self,
+        name: &str,
+        descriptor: ParameterTypeDescriptor,
+    ) -> ParametersDescriptor {
+        self.1.insert(name.to_string());
+        self.with(name, descriptor)
+    }
+    pub fn is_live(&self, name: &str) -> bool { self.1.contains(name) }
     pub fn with_interpretation(self) -> ParametersDescriptor {
         self
             // general metadata
@@ -405,10 +532,72 @@ impl ParametersDescriptor {
 
 
             // color interpretation
-            .with("bayer", WithDefault(StringParameter, StringValue("RGBG".to_string())))
+            .with("bayer", WithDefault(BayerPattern(), StringValue("RGBG".to_string())))
             .with("rgb", Flag())
             .with("rgba", Flag())
     }
+
+    /// `cache-frames` keeps the frames that were read in a least recently used
+    /// cache of at most `cache-max-bytes` (1 GiB by default)
+    pub fn with_frame_cache(self) -> ParametersDescriptor {
+        self.with("cache-frames", Flag())
+            .with("cache-max-bytes", WithDefault(NaturalGreaterZero(), IntRangeValue(1 << 30)))
+    }
+
+    /// what a sink does with frames that fail: `fail`, `skip`, `retry:<n>` or
+    /// `repeat-last`
+    pub fn with_error_policy(self) -> ParametersDescriptor {
+        self.with("on-error", WithDefault(StringParameter, StringValue("skip".to_string())))
+    }
+
+    /// `number-of-frames` limits the output to the frame indices `0..n`, with
+    /// `exact-number-of-frames` further frames are pulled until n frames were
+    /// written
+    pub fn with_frame_count(self) -> ParametersDescriptor {
+        self.with("number-of-frames", Optional(NaturalWithZero()))
+            .with("exact-number-of-frames", Flag())
+            .with("max-consecutive-failures", WithDefault(NaturalWithZero(), IntRangeValue(16)))
+    }
+}
+
+/// A parameter that was given to a node, but that the node neither read nor
+/// declared. Usually a typo.
+#[derive(thiserror::Error, Debug)]
+#[error("{node} has no parameter {parameter}{}", did_you_mean(.suggestion))]
+pub struct UnknownParameterError {
+    pub node: String,
+    pub parameter: String,
+    pub suggestion: Option<String>,
+}
+
+impl ParametersDescriptor {
+    /// Checks the keys that were `given` to a node against the keys it
+    /// `used`. Declared parameters may stay unused, some are only read
+    /// depending on others.
+    pub(crate) fn check_unused(
+        &self,
+        node: &str,
+        given: &[String],
+        used: &HashSet<String>,
+    ) -> Result<(), UnknownParameterError> {
+        let mut unknown = given
+            .iter()
+            .filter(|key| !used.contains(*key) && !self.0.contains_key(*key))
+            .collect::<Vec<_>>();
+        unknown.sort();
+        match unknown.first() {
+            None => Ok(()),
+            Some(parameter) => Err(UnknownParameterError {
+                node: node.to_string(),
+                parameter: parameter.to_string(),
+                suggestion: closest_match(
+                    parameter,
+                    self.0.keys().filter(|key| !given.contains(*key)),
+                )
+                .cloned(),
+            }),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -416,11 +605,17 @@ pub struct ParameterizableDescriptor {
     pub name: String,
     pub description: Option<String>,
     pub parameters_descriptor: ParametersDescriptor,
+    /// the named outputs, empty if the node only has its unnamed output
+    pub outputs: Vec<String>,
 }
 
 pub trait Parameterizable {
     const NAME: Option<&'static str> = None;
     const DESCRIPTION: Option<&'static str> = None;
+    /// The names of the outputs of the node, which other nodes refer to as
+    /// `<node.<output>`. The first one is the output `<node` refers to, the
+    /// others are returned by `ProcessingNode::output_port`.
+    const OUTPUTS: &'static [&'static str] = &[];
 
     fn describe_parameters() -> ParametersDescriptor;
     fn from_parameters(
@@ -431,6 +626,18 @@ pub trait Parameterizable {
     where
         Self: Sized;
 
+    /// Derives the interpretation of the produced frames from the parameters
+    /// and the interpretations of the inputs, without creating the node. It is
+    /// used to label rendered graphs, so it must not open files or devices.
+    /// The default covers sources that declare their interpretation with
+    /// `with_interpretation`.
+    fn describe_output(
+        mut parameters: Parameters,
+        _inputs: &HashMap<String, FrameInterpretation>,
+    ) -> Option<FrameInterpretation> {
+        parameters.get_interpretation().ok()
+    }
+
     fn get_name() -> String {
         Self::NAME
             .map(|v| v.to_string())
@@ -441,6 +648,7 @@ pub trait Parameterizable {
             name: Self::get_name(),
             description: Self::DESCRIPTION.map(|s| s.to_string()),
             parameters_descriptor: Self::describe_parameters(),
+            outputs: Self::OUTPUTS.iter().map(|output| output.to_string()).collect(),
         }
     }
 }
@@ -476,4 +684,39 @@ pub mod prelude {
     pub fn NaturalGreaterZero() -> ParameterType { IntRange(1, i64::MAX) }
     pub fn U8() -> ParameterType { IntRange(0, u8::MAX as i64) }
     pub fn PositiveReal() -> ParameterType { FloatRange(0.0, f64::MAX) }
+    pub fn BayerPattern() -> ParameterType { Choice(&["RGBG", "BGRG", "GBGR", "GRGB"]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParametersDescriptor;
+    use crate::pipeline_processing::{
+        processing_graph::{ProcessingNodeConfig, SerdeNodeConfig},
+        puller::FrameCount,
+    };
+
+    #[test]
+    fn test_frame_count() {
+        let frame_count = |yaml: &str| {
+            let config: ProcessingNodeConfig<String> =
+                serde_yaml::from_str::<SerdeNodeConfig>(yaml).unwrap().into();
+            config
+                .parameters
+                .add_defaults(ParametersDescriptor::new().with_frame_count())
+                .unwrap()
+                .get_frame_count()
+                .unwrap()
+        };
+
+        assert_eq!(frame_count("{type: Sink}"), FrameCount::All);
+        assert_eq!(frame_count("{type: Sink, number-of-frames: 10}"), FrameCount::Indices(10));
+        assert_eq!(
+            frame_count("{type: Sink, number-of-frames: 10, exact-number-of-frames: true}"),
+            FrameCount::Exact { frames: 10, max_consecutive_failures: 16 }
+        );
+        assert_eq!(
+            frame_count("{type: Sink, number-of-frames: 10, exact-number-of-frames: false}"),
+            FrameCount::Indices(10)
+        );
+    }
 }
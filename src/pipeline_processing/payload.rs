@@ -1,4 +1,5 @@
-use anyhow::anyhow;
+use crate::pipeline_processing::frame::FrameInterpretation;
+use anyhow::{anyhow, Result};
 use std::{
     any::{type_name, Any},
     sync::Arc,
@@ -28,6 +29,59 @@ impl Payload {
             )
         })
     }
+    pub fn downcast_ref<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.data.downcast_ref::<T>()
+    }
+}
+
+/// The statically known type of the payloads a node produces. It is used to
+/// check a pipeline for incompatible connections before it is run.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PayloadType {
+    /// A `Frame` (in cpu or gpu memory). The interpretation is `None` if it is
+    /// only known at runtime (for example when it is read from a file header).
+    Frame(Option<FrameInterpretation>),
+    /// A `Vec<Payload>` with the given element types.
+    List(Vec<PayloadType>),
+    /// Nothing is known about the payload before the pipeline runs.
+    Unknown,
+}
+
+impl PayloadType {
+    pub fn frame(interpretation: FrameInterpretation) -> Self { Self::Frame(Some(interpretation)) }
+
+    /// Checks that this is a frame and returns its interpretation if it is
+    /// known statically.
+    pub fn expect_frame(&self) -> Result<Option<FrameInterpretation>> {
+        match self {
+            Self::Frame(interpretation) => Ok(*interpretation),
+            Self::Unknown => Ok(None),
+            Self::List(elements) => {
+                Err(anyhow!("expected a frame but got a list of payloads {elements:?}"))
+            }
+        }
+    }
+
+    /// Checks that this is a frame and derives the type of the frame produced
+    /// from it.
+    pub fn map_frame(
+        &self,
+        func: impl FnOnce(FrameInterpretation) -> Result<FrameInterpretation>,
+    ) -> Result<Self> {
+        Ok(Self::Frame(self.expect_frame()?.map(func).transpose()?))
+    }
+
+    /// Checks that this is a list of payloads and returns its element types if
+    /// they are known statically.
+    pub fn expect_list(&self) -> Result<Option<&[PayloadType]>> {
+        match self {
+            Self::List(elements) => Ok(Some(elements)),
+            Self::Unknown => Ok(None),
+            Self::Frame(interpretation) => {
+                Err(anyhow!("expected a list of payloads but got a frame {interpretation:?}"))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
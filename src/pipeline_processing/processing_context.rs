@@ -1,12 +1,21 @@
 use crate::pipeline_processing::{
-    buffers::{CpuBuffer, GpuBuffer},
+    buffers::{BufferPool, CpuBuffer, GpuBuffer, PooledBuffer},
     frame::Frame,
     payload::Payload,
     prioritized_executor::PrioritizedReactor,
+    puller::ErrorLog,
 };
 use anyhow::{anyhow, Result};
-use parking_lot::lock_api::RwLock;
-use std::{future::Future, sync::Arc};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+        Weak,
+    },
+};
+use tokio::sync::Notify;
 use vulkano::{
     buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer},
     command_buffer::{
@@ -69,11 +78,46 @@ impl std::fmt::Display for Priority {
     }
 }
 
+#[derive(Default)]
+struct Cancellation {
+    cancelled: AtomicBool,
+    notify: Notify,
+    parent: Option<Arc<Cancellation>>,
+    children: Mutex<Vec<Weak<Cancellation>>>,
+}
+
+impl Cancellation {
+    fn child(self: &Arc<Self>) -> Arc<Self> {
+        let child = Arc::new(Cancellation { parent: Some(self.clone()), ..Default::default() });
+        let mut children = self.children.lock().unwrap();
+        children.retain(|child| child.strong_count() > 0);
+        children.push(Arc::downgrade(&child));
+        child
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self.parent.as_ref().map_or(false, |parent| parent.is_cancelled())
+    }
+
+    fn notify(&self) {
+        self.notify.notify_waiters();
+        for child in self.children.lock().unwrap().iter().filter_map(Weak::upgrade) {
+            child.notify();
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ProcessingContext {
     vulkan_device: Option<VulkanContext>,
     prioritized_reactor: PrioritizedReactor<Priority>,
     tokio_rt_handle: Arc<tokio::runtime::Runtime>,
+    cancellation: Arc<Cancellation>,
+    buffer_pool: Arc<BufferPool>,
+    errors: Arc<ErrorLog>,
+    // the frame the pullers start at
+    first_frame: u64,
 }
 impl Default for ProcessingContext {
     fn default() -> Self {
@@ -148,6 +192,11 @@ impl ProcessingContext {
             .unwrap_or_else(|_| num_cpus::get());
         println!("using {threads} threads");
 
+        let buffer_pool_mb = std::env::var("RECORDER_BUFFER_POOL_MB")
+            .map_err(|_| ())
+            .and_then(|v| v.parse::<usize>().map_err(|_| ()))
+            .unwrap_or(1024);
+
 
         if let Some(vulkan_context) = &vulkan_context {
             println!(
@@ -163,35 +212,50 @@ impl ProcessingContext {
             vulkan_device: vulkan_context,
             prioritized_reactor: PrioritizedReactor::start(threads),
             tokio_rt_handle: Arc::new(tokio::runtime::Runtime::new().unwrap()),
+            cancellation: Default::default(),
+            buffer_pool: BufferPool::new(buffer_pool_mb * 1024 * 1024),
+            errors: Default::default(),
+            first_frame: 0,
         }
     }
 
     /// # Safety
     /// Only safe if you initialize the memory
     pub unsafe fn get_uninit_cpu_buffer(&self, len: usize) -> CpuBuffer {
-        if let Some(vulkan_context) = &self.vulkan_device {
-            CpuAccessibleBuffer::uninitialized_array(
-                vulkan_context.device.clone(),
-                len as _,
-                BufferUsage {
-                    storage_buffer: true,
-                    storage_texel_buffer: true,
-                    transfer_src: true,
-                    transfer_dst: true,
-                    ..BufferUsage::none()
-                },
-                true,
-            )
-            .unwrap()
-            .into()
-        } else {
-            let mut vec: Vec<u8> = Vec::with_capacity(len);
-            unsafe {
-                vec.set_len(len);
+        self.buffer_pool.get(len, |size| {
+            if let Some(vulkan_context) = &self.vulkan_device {
+                let buffer = CpuAccessibleBuffer::uninitialized_array(
+                    vulkan_context.device.clone(),
+                    size as _,
+                    BufferUsage {
+                        storage_buffer: true,
+                        storage_texel_buffer: true,
+                        transfer_src: true,
+                        transfer_dst: true,
+                        ..BufferUsage::none()
+                    },
+                    true,
+                )
+                .unwrap();
+                PooledBuffer::Vulkan(Arc::try_unwrap(buffer).unwrap())
+            } else {
+                let mut vec: Vec<u8> = Vec::with_capacity(size);
+                unsafe {
+                    vec.set_len(size);
+                }
+                PooledBuffer::Vec(vec)
             }
-            CpuBuffer::Vec(Arc::new(RwLock::new(vec)))
-        }
+        })
     }
+
+    /// The pool `get_uninit_cpu_buffer` takes its buffers from. The size of
+    /// the pool can be set with the `RECORDER_BUFFER_POOL_MB` environment
+    /// variable.
+    pub fn buffer_pool(&self) -> &BufferPool { &self.buffer_pool }
+
+    /// The errors the sinks ran into while pulling frames.
+    pub fn errors(&self) -> &ErrorLog { &self.errors }
+
     fn to_cpu_buffer_frame(&self, frame: Arc<Frame<GpuBuffer>>) -> Result<Frame<CpuBuffer>> {
         let (device, queues) = self.require_vulkan()?;
         let queue =
@@ -217,7 +281,11 @@ impl ProcessingContext {
         // dropping this future blocks this thread until the gpu finished the work
         drop(future);
 
-        Ok(Frame { interpretation: frame.interpretation.clone(), storage: buffer })
+        Ok(Frame {
+            interpretation: frame.interpretation.clone(),
+            storage: buffer,
+            metadata: frame.metadata.clone(),
+        })
     }
     pub fn ensure_cpu_buffer_frame(&self, payload: &Payload) -> Result<Arc<Frame<CpuBuffer>>> {
         if let Ok(frame) = payload.downcast::<Frame<CpuBuffer>>() {
@@ -252,5 +320,80 @@ impl ProcessingContext {
         self.tokio_rt_handle.block_on(fut)
     }
 
+    /// Runs blocking work like waiting for a child process on a thread
+    /// outside of the reactor.
+    pub async fn spawn_blocking<O: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> O + Send + 'static,
+    ) -> Result<O> {
+        Ok(self.tokio_rt_handle.spawn_blocking(f).await?)
+    }
+
     pub fn num_threads(&self) -> usize { self.prioritized_reactor.num_threads }
+
+    /// Asks the pipeline to stop. The pullers stop requesting new frames but
+    /// still deliver the frames that are already in flight, so the sinks can
+    /// finish cleanly.
+    pub fn cancel(&self) {
+        self.cancellation.cancelled.store(true, Ordering::Relaxed);
+        self.cancellation.notify();
+    }
+
+    pub fn is_cancelled(&self) -> bool { self.cancellation.is_cancelled() }
+
+    /// Returns a context that shares everything with this one but can be
+    /// cancelled on its own. It is also cancelled if this context is. Its
+    /// errors are also recorded in the error log of this context.
+    pub fn child(&self) -> Self {
+        Self {
+            cancellation: self.cancellation.child(),
+            errors: Arc::new(self.errors.child()),
+            ..self.clone()
+        }
+    }
+
+    /// The frame the pullers of this context start at, a sink that was armed
+    /// again resumes after the frames of its previous run.
+    pub fn first_frame(&self) -> u64 { self.first_frame }
+
+    pub(crate) fn starting_at(self, first_frame: u64) -> Self { Self { first_frame, ..self } }
+
+    /// Resolves once `cancel` was called.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.cancellation.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Cancels the pipeline on the first SIGINT or SIGTERM and exits the
+    /// process immediately on the second one.
+    pub fn cancel_on_signals(&self) {
+        let context = self.clone();
+        self.tokio_rt_handle.spawn(async move {
+            let signal = || async {
+                #[cfg(unix)]
+                {
+                    use tokio::signal::unix::{signal, SignalKind};
+                    let mut terminate = signal(SignalKind::terminate()).unwrap();
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => {},
+                        _ = terminate.recv() => {},
+                    }
+                }
+                #[cfg(not(unix))]
+                tokio::signal::ctrl_c().await.unwrap();
+            };
+
+            signal().await;
+            eprintln!("\nstopping after the frames in flight, send the signal again to force exit");
+            context.cancel();
+            signal().await;
+            eprintln!("\nforced exit");
+            std::process::exit(130);
+        });
+    }
 }
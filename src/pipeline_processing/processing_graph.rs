@@ -1,18 +1,25 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use futures::StreamExt;
 use serde::{Deserialize, Deserializer};
 use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    str::FromStr,
+    sync::{Arc, Mutex},
 };
 
 use crate::{
-    nodes::create_node_from_name,
+    nodes::{create_node_from_name, describe_output_from_name, list_available_nodes},
     pipeline_processing::{
-        node::{Node, NodeID, ProgressUpdate},
-        parametrizable::{ParameterValue, Parameters},
+        frame::FrameInterpretation,
+        metrics::{GraphMetrics, NodeMetrics},
+        node::{InputProcessingNode, Node, NodeID, ProgressUpdate},
+        parametrizable::{list_input_name, ParameterValue, Parameters},
         processing_context::ProcessingContext,
+        sink_control::SinkControl,
+        subgraph::Subgraph,
     },
+    util::lru::LruStats,
 };
 
 #[derive(Debug)]
@@ -105,48 +112,190 @@ pub struct SerdeNodeConfig {
 
 impl From<SerdeNodeConfig> for ProcessingNodeConfig<String> {
     fn from(node_config: SerdeNodeConfig) -> Self {
-        Self {
-            name: node_config.ty,
-            parameters: Parameters::new(
-                node_config
-                    .parameters
-                    .clone()
-                    .into_iter()
-                    .filter_map(|(name, param)| match param {
-                        SerdeNodeParam::NodeInput(_) => None,
-                        param => param.try_into().ok().map(|v| (name, v)),
-                    })
-                    .collect(),
-            ),
-            inputs: node_config
-                .parameters
-                .into_iter()
-                .filter_map(|(name, param)| match param {
-                    SerdeNodeParam::NodeInput(i) => Some((name, i)),
-                    _ => None,
-                })
-                .collect(),
+        let mut parameters = HashMap::new();
+        let mut inputs = HashMap::new();
+        for (name, param) in node_config.parameters {
+            match param {
+                SerdeNodeParam::NodeInput(input) => {
+                    inputs.insert(name, input);
+                }
+                // a list of node inputs, every element becomes an input of its own
+                SerdeNodeParam::List(list)
+                    if !list.is_empty()
+                        && list
+                            .iter()
+                            .all(|param| matches!(param, SerdeNodeParam::NodeInput(_))) =>
+                {
+                    for (index, param) in list.into_iter().enumerate() {
+                        if let SerdeNodeParam::NodeInput(input) = param {
+                            inputs.insert(list_input_name(&name, index), input);
+                        }
+                    }
+                }
+                param => {
+                    if let Ok(value) = param.try_into() {
+                        parameters.insert(name, value);
+                    }
+                }
+            }
+        }
+        Self { name: node_config.ty, parameters: Parameters::new(parameters), inputs }
+    }
+}
+
+
+/// Errors found while validating the structure of a graph before any node is
+/// created.
+#[derive(thiserror::Error, Debug)]
+pub enum GraphError {
+    #[error(
+        "input {input} of node {node} refers to the unknown node {target}{}",
+        did_you_mean(.suggestion)
+    )]
+    UnknownInput { node: String, input: String, target: String, suggestion: Option<String> },
+    #[error(
+        "input {input} of node {node} refers to the output {output} of {target}, which has no \
+         such output{}",
+        did_you_mean(.suggestion)
+    )]
+    UnknownOutput {
+        node: String,
+        input: String,
+        target: String,
+        output: String,
+        suggestion: Option<String>,
+    },
+    #[error("the processing graph contains a cycle: {}", .path.join(" -> "))]
+    Cycle { path: Vec<String> },
+}
+
+pub(crate) fn did_you_mean(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(", did you mean {suggestion}?"),
+        None => String::new(),
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GraphWarning {
+    #[error("the output of node {node} is not used by any sink")]
+    NotFeedingSink { node: String },
+}
+
+/// The candidate `target` most likely is a misspelling of, if any is close
+/// enough.
+pub(crate) fn closest_match<T: AsRef<str>>(
+    target: &str,
+    candidates: impl IntoIterator<Item = T>,
+) -> Option<T> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(target, candidate.as_ref()), candidate))
+        .filter(|(distance, _)| *distance <= target.len().max(3) / 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance, used to suggest what a misspelled name was meant to
+/// refer to.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The given nodes and all nodes they pull from, directly or indirectly.
+fn upstream_nodes(nodes: &[NodeID], inputs_of: &HashMap<NodeID, Vec<NodeID>>) -> HashSet<NodeID> {
+    let mut upstream = HashSet::new();
+    let mut to_visit = nodes.to_vec();
+    while let Some(idx) = to_visit.pop() {
+        if upstream.insert(idx) {
+            to_visit.extend(inputs_of.get(&idx).into_iter().flatten());
         }
     }
+    upstream
 }
 
+/// Node ids that can also refer to a named output of a node, written as
+/// `<node>.<output>`.
+pub trait OutputId: Sized {
+    fn split_output(&self) -> Option<(Self, &str)>;
+}
+
+impl OutputId for String {
+    fn split_output(&self) -> Option<(Self, &str)> {
+        self.rsplit_once('.').map(|(node, output)| (node.to_string(), output))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            other => bail!("unknown graph format {other}, expected dot or mermaid"),
+        }
+    }
+}
 
 pub struct ProcessingGraphBuilder<IdTy> {
     nodes: HashMap<IdTy, ProcessingNodeConfig<IdTy>>,
     node_ids: HashMap<IdTy, NodeID>,
+    // the outputs of sub-graphs, mapped to the nodes inside of them. inputs
+    // are rewritten on insertion, so no node refers to an alias.
+    aliases: HashMap<IdTy, IdTy>,
+    allow_unknown_parameters: bool,
 }
 
 impl<IdTy> Default for ProcessingGraphBuilder<IdTy> {
-    fn default() -> Self { Self { nodes: HashMap::new(), node_ids: HashMap::new() } }
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            node_ids: HashMap::new(),
+            aliases: HashMap::new(),
+            allow_unknown_parameters: false,
+        }
+    }
 }
 
 impl<IdTy> ProcessingGraphBuilder<IdTy>
 where
-    IdTy: Eq + std::hash::Hash + std::fmt::Debug + Clone,
+    IdTy: Eq + std::hash::Hash + std::fmt::Debug + Clone + OutputId,
 {
     pub fn new() -> Self { Default::default() }
 
-    pub fn add(&mut self, name: IdTy, config: ProcessingNodeConfig<IdTy>) -> Result<NodeID> {
+    /// Ignore parameters no node knows instead of failing the build, for
+    /// configs written for newer versions.
+    pub fn allow_unknown_parameters(&mut self, allow: bool) {
+        self.allow_unknown_parameters = allow;
+    }
+
+    pub fn add(&mut self, name: IdTy, mut config: ProcessingNodeConfig<IdTy>) -> Result<NodeID> {
+        if self.aliases.contains_key(&name) {
+            bail!("tried to add node {name:?}, but a sub-graph output with that name exists");
+        }
+        for input in config.inputs.values_mut() {
+            if let Some(target) = self.aliases.get(input) {
+                *input = target.clone();
+            }
+        }
         let node_id: NodeID = self.nodes.len().into();
         match self.nodes.entry(name.clone()) {
             std::collections::hash_map::Entry::Occupied(e) => {
@@ -163,42 +312,355 @@ where
         }
     }
 
+    /// Makes inputs referring to `alias` refer to `target` instead.
+    fn add_alias(&mut self, alias: IdTy, target: IdTy) -> Result<()> {
+        if self.nodes.contains_key(&alias) || self.aliases.contains_key(&alias) {
+            bail!("tried to add the sub-graph output {alias:?}, but the name is already used");
+        }
+        let target = self.aliases.get(&target).cloned().unwrap_or(target);
+        for existing in self.aliases.values_mut() {
+            if *existing == alias {
+                *existing = target.clone();
+            }
+        }
+        for node in self.nodes.values_mut() {
+            for input in node.inputs.values_mut() {
+                if *input == alias {
+                    *input = target.clone();
+                }
+            }
+        }
+        self.aliases.insert(alias, target);
+        Ok(())
+    }
+
+    /// The ids the nodes will have in the built `ProcessingGraph`.
+    pub fn node_ids(&self) -> &HashMap<IdTy, NodeID> { &self.node_ids }
+
+    /// The node an input refers to and which of its named outputs, `None` for
+    /// the unnamed output. A node whose name contains a `.` takes precedence.
+    fn resolve_output(&self, id: &IdTy) -> Option<(IdTy, Option<String>)> {
+        if self.node_ids.contains_key(id) {
+            return Some((id.clone(), None));
+        }
+        let (node, output) = id.split_output()?;
+        let output = output.to_string();
+        self.node_ids.contains_key(&node).then_some((node, Some(output)))
+    }
+
+    /// The interpretation of the frames a node produces on its unnamed output,
+    /// as far as it follows from the parameters (see
+    /// `Parameterizable::describe_output`). Results are memoized in `known`.
+    fn describe_output(
+        &self,
+        id: &IdTy,
+        known: &mut HashMap<IdTy, Option<FrameInterpretation>>,
+    ) -> Option<FrameInterpretation> {
+        if let Some(interpretation) = known.get(id) {
+            return *interpretation;
+        }
+        let node = self.nodes.get(id)?;
+        // guards against cycles, which `validate` reports
+        known.insert(id.clone(), None);
+        let mut inputs = HashMap::new();
+        for (input_name, input_id) in &node.inputs {
+            if let Some((target, None)) = self.resolve_output(input_id) {
+                if let Some(interpretation) = self.describe_output(&target, known) {
+                    inputs.insert(input_name.clone(), interpretation);
+                }
+            }
+        }
+        let interpretation =
+            describe_output_from_name(&node.name, node.parameters.clone_for_same_puller(), &inputs);
+        known.insert(id.clone(), interpretation);
+        interpretation
+    }
+
+    /// Renders the configured graph without instantiating any node. The
+    /// parameters are shown with their defaults filled in. Edges are labeled
+    /// with the frame interpretation where it follows from the parameters of
+    /// the nodes before them.
+    pub fn render(&self, format: GraphFormat) -> Result<String> {
+        let available_nodes = list_available_nodes();
+
+        let nodes = self.sorted_nodes();
+
+        let mut interpretations = HashMap::new();
+        let mut out = String::new();
+        match format {
+            GraphFormat::Dot => writeln!(out, "digraph pipeline {{")?,
+            GraphFormat::Mermaid => writeln!(out, "flowchart LR")?,
+        }
+        for (id, node) in &nodes {
+            let descriptor = &available_nodes
+                .get(&node.name)
+                .ok_or_else(|| anyhow!("no node named {} found", node.name))?
+                .parameters_descriptor;
+            let parameters = node
+                .parameters
+                .clone_for_same_puller()
+                .add_defaults(descriptor.clone())
+                .with_context(|| format!("invalid parameters for node {id:?}"))?;
+            let mut lines = vec![format!("{id:?}: {}", node.name)];
+            let mut values = parameters
+                .iter()
+                .map(|(key, value)| format!("{key} = {}", value.to_string()))
+                .collect::<Vec<_>>();
+            values.sort();
+            lines.extend(values);
+
+            self.describe_output(id, &mut interpretations);
+
+            let idx = usize::from(self.node_ids[*id]);
+            match format {
+                GraphFormat::Dot => {
+                    let label = lines
+                        .iter()
+                        .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+                        .collect::<Vec<_>>()
+                        .join("\\l");
+                    writeln!(out, "    n{idx} [shape=box, label=\"{label}\\l\"];")?
+                }
+                GraphFormat::Mermaid => {
+                    let label = lines
+                        .iter()
+                        .map(|line| line.replace('"', "#quot;"))
+                        .collect::<Vec<_>>()
+                        .join("<br/>");
+                    writeln!(out, "    n{idx}[\"{label}\"]")?
+                }
+            }
+        }
+
+        for (id, node) in &nodes {
+            let idx = usize::from(self.node_ids[*id]);
+            let mut inputs = node.inputs.iter().collect::<Vec<_>>();
+            inputs.sort_by_key(|(input_name, _)| input_name.as_str());
+            for (input_name, input_id) in inputs {
+                let (target, output) = self.resolve_output(input_id).ok_or_else(|| {
+                    anyhow!("input {input_name} of {id:?} refers to unknown node {input_id:?}")
+                })?;
+                let input_idx = self.node_ids[&target];
+                // the interpretations are only known for the unnamed outputs
+                let label = match (output, interpretations.get(&target).copied().flatten()) {
+                    (Some(output), _) => format!("{output} -> {input_name}"),
+                    (None, Some(interpretation)) => format!("{input_name}: {interpretation}"),
+                    (None, None) => input_name.clone(),
+                };
+                let input_idx = usize::from(input_idx);
+                match format {
+                    GraphFormat::Dot => writeln!(
+                        out,
+                        "    n{input_idx} -> n{idx} [label=\"{}\"];",
+                        label.replace('"', "\\\"")
+                    )?,
+                    GraphFormat::Mermaid => writeln!(
+                        out,
+                        "    n{input_idx} -->|\"{}\"| n{idx}",
+                        label.replace('"', "#quot;")
+                    )?,
+                }
+            }
+        }
+        if let GraphFormat::Dot = format {
+            writeln!(out, "}}")?
+        }
+
+        Ok(out)
+    }
+
+    fn sorted_nodes(&self) -> Vec<(&IdTy, &ProcessingNodeConfig<IdTy>)> {
+        let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+        nodes.sort_by_key(|(id, _)| usize::from(self.node_ids[*id]));
+        nodes
+    }
+
+    /// Checks that all inputs refer to existing nodes and that the graph is
+    /// acyclic.
+    pub fn validate(&self) -> Result<(), GraphError> {
+        let available_nodes = list_available_nodes();
+        let nodes = self.sorted_nodes();
+
+        // the nodes the inputs of every node refer to, ordered by input name
+        let mut edges = HashMap::new();
+        for (id, node) in &nodes {
+            let mut inputs = node.inputs.iter().collect::<Vec<_>>();
+            inputs.sort_by_key(|(input_name, _)| input_name.as_str());
+            let mut targets = vec![];
+            for (input_name, input_id) in inputs {
+                let (target, output) = self.resolve_output(input_id).ok_or_else(|| {
+                    let target = format!("{input_id:?}");
+                    let suggestion = closest_match(
+                        &target,
+                        nodes.iter().map(|(candidate, _)| format!("{candidate:?}")),
+                    );
+                    GraphError::UnknownInput {
+                        node: format!("{id:?}"),
+                        input: input_name.clone(),
+                        target,
+                        suggestion,
+                    }
+                })?;
+                let (target, target_node) = self.nodes.get_key_value(&target).unwrap();
+                // nodes of unknown types fail later, when they are created
+                let outputs = available_nodes.get(&target_node.name).map(|node| &node.outputs);
+                if let (Some(output), Some(outputs)) = (output, outputs) {
+                    if !outputs.contains(&output) {
+                        return Err(GraphError::UnknownOutput {
+                            node: format!("{id:?}"),
+                            input: input_name.clone(),
+                            target: format!("{target:?}"),
+                            suggestion: closest_match(&output, outputs).cloned(),
+                            output,
+                        });
+                    }
+                }
+                targets.push(target);
+            }
+            edges.insert(*id, targets);
+        }
+
+        // depth first search along the inputs, a node that is reached again
+        // while it is still on the stack closes a cycle
+        fn visit<'a, IdTy: Eq + std::hash::Hash + std::fmt::Debug>(
+            id: &'a IdTy,
+            edges: &HashMap<&'a IdTy, Vec<&'a IdTy>>,
+            stack: &mut Vec<&'a IdTy>,
+            done: &mut HashSet<&'a IdTy>,
+        ) -> Result<(), GraphError> {
+            if done.contains(id) {
+                return Ok(());
+            }
+            if let Some(position) = stack.iter().position(|on_stack| *on_stack == id) {
+                let path = stack[position..]
+                    .iter()
+                    .chain(std::iter::once(&id))
+                    .map(|id| format!("{id:?}"))
+                    .collect();
+                return Err(GraphError::Cycle { path });
+            }
+            stack.push(id);
+            for &input_id in &edges[id] {
+                visit(input_id, edges, stack, done)?;
+            }
+            stack.pop();
+            done.insert(id);
+            Ok(())
+        }
+
+        let mut done = HashSet::new();
+        for (id, _) in nodes {
+            visit(id, &edges, &mut vec![], &mut done)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a warning for every node from which no sink can be reached.
+    fn find_unused(
+        &self,
+        sinks: &[NodeID],
+        inputs_of: &HashMap<NodeID, Vec<NodeID>>,
+    ) -> Vec<GraphWarning> {
+        let used = upstream_nodes(sinks, inputs_of);
+        let mut unused =
+            self.node_ids.iter().filter(|(_, idx)| !used.contains(*idx)).collect::<Vec<_>>();
+        unused.sort_by_key(|(_, idx)| usize::from(**idx));
+        unused
+            .into_iter()
+            .map(|(id, _)| GraphWarning::NotFeedingSink { node: format!("{id:?}") })
+            .collect()
+    }
+
     pub fn build(mut self, ctx: &ProcessingContext) -> Result<ProcessingGraph> {
+        self.validate()?;
+
         if self.nodes.is_empty() {
-            Ok(ProcessingGraph { nodes: HashMap::new(), sinks: vec![] })
+            Ok(ProcessingGraph {
+                nodes: HashMap::new(),
+                sinks: vec![],
+                sink_controls: HashMap::new(),
+                parameters: HashMap::new(),
+                warnings: vec![],
+                metrics: Arc::new(GraphMetrics::new(vec![])),
+            })
         } else {
+            let mut node_metrics =
+                (0..self.nodes.len()).map(|_| NodeMetrics::default()).collect::<Vec<_>>();
+            for (id, node) in self.nodes.iter() {
+                let metrics = &mut node_metrics[usize::from(self.node_ids[id])];
+                metrics.name = format!("{id:?}");
+                metrics.node_type = node.name.clone();
+            }
+            let metrics = Arc::new(GraphMetrics::new(node_metrics));
+
             let mut is_input_to = HashMap::<_, Vec<_>>::new();
+            let mut inputs_of = HashMap::<_, Vec<_>>::new();
 
             for (id, node) in self.nodes.iter() {
                 let idx = self.node_ids[id];
                 for input in node.inputs.values() {
-                    is_input_to.entry(self.node_ids[input]).or_default().push(idx);
+                    let (target, _) = self.resolve_output(input).unwrap();
+                    is_input_to.entry(self.node_ids[&target]).or_default().push(idx);
+                    inputs_of.entry(idx).or_default().push(self.node_ids[&target]);
                 }
             }
 
+            let available_nodes = list_available_nodes();
+            let node_types = self
+                .nodes
+                .iter()
+                .map(|(id, node)| (self.node_ids[id], node.name.clone()))
+                .collect::<HashMap<_, _>>();
             let mut built_nodes = HashMap::<NodeID, Node>::new();
             let mut sinks = vec![];
+            let mut parameters = HashMap::new();
 
             let mut avail: HashSet<IdTy> = self.node_ids.keys().cloned().collect();
             let mut queue = vec![];
-            queue.push({
-                let v = avail.iter().next().cloned().unwrap();
-                avail.take(&v).unwrap()
-            });
 
-            while !queue.is_empty() {
+            loop {
+                if queue.is_empty() {
+                    match avail.iter().next() {
+                        Some(id) => queue.push(id.clone()),
+                        None => break,
+                    }
+                }
                 let id = queue.last().unwrap().clone();
                 let idx = self.node_ids[&id];
+                // a node that was queued by several of its consumers (or for
+                // several inputs of one node) is only built once
+                if built_nodes.contains_key(&idx) {
+                    queue.pop();
+                    continue;
+                }
                 let mut missing = vec![];
                 let mut finished = HashMap::new();
 
                 let node = self.nodes.remove(&id).unwrap();
 
                 for (name, input_id) in &node.inputs {
-                    if let Some(node) = built_nodes.get(&self.node_ids[input_id]) {
-                        finished.insert(name.clone(), node.clone());
-                    } else {
-                        missing.push(input_id.clone())
+                    let (target, output) = self.resolve_output(input_id).unwrap();
+                    let input_idx = self.node_ids[&target];
+                    if let Some(input_node) = built_nodes.get(&input_idx) {
+                        let mut input_node = input_node.assert_input_node().with_context(|| {
+                            format!("could not convert input {name} to a input node")
+                        })?;
+                        let first_output = available_nodes
+                            .get(&node_types[&input_idx])
+                            .and_then(|node| node.outputs.first());
+                        if let Some(output) = output.filter(|output| Some(output) != first_output) {
+                            input_node = input_node.output_port(&output).with_context(|| {
+                                format!("could not get the output {output} of {target:?}")
+                            })?;
+                        }
+                        finished.insert(
+                            name.clone(),
+                            InputProcessingNode::new(idx, input_node)
+                                .with_metrics(input_idx, metrics.clone()),
+                        );
+                    } else if !missing.contains(&target) {
+                        missing.push(target)
                     }
                 }
 
@@ -206,14 +668,35 @@ where
                     queue.append(&mut missing);
                     self.nodes.insert(id, node);
                 } else {
+                    if let Some(descriptor) = available_nodes.get(&node.name) {
+                        let values = node
+                            .parameters
+                            .clone_for_same_puller()
+                            .add_defaults(descriptor.parameters_descriptor.clone())
+                            .with_context(|| format!("couldn't create node {id:?}"))?
+                            .iter()
+                            .map(|(key, value)| (key.clone(), value.to_string()))
+                            .collect();
+                        parameters.insert(idx, Mutex::new(values));
+                    }
                     let built_node = create_node_from_name(
                         &node.name,
-                        idx,
                         node.parameters,
                         finished,
                         is_input_to.entry(idx).or_default(),
                         ctx,
-                    )?;
+                        self.allow_unknown_parameters,
+                    )
+                    .with_context(|| format!("couldn't create node {id:?}"))?;
+                    built_node.check_types().with_context(|| {
+                        let edges = node
+                            .inputs
+                            .iter()
+                            .map(|(name, input_id)| format!("{input_id:?} -> {id:?}.{name}"))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!("incompatible connection into node {id:?} ({}): {edges}", node.name)
+                    })?;
                     if built_node.is_sink() {
                         sinks.push(idx);
                     }
@@ -222,21 +705,100 @@ where
 
                     avail.remove(&id);
                     queue.pop().unwrap();
+                }
+            }
 
-                    if queue.is_empty() && !avail.is_empty() {
-                        queue.push(avail.iter().next().unwrap().clone())
-                    }
+            let warnings = self.find_unused(&sinks, &inputs_of);
+            let sink_controls = sinks
+                .iter()
+                .map(|id| {
+                    let holding = upstream_nodes(&[*id], &inputs_of).into_iter().find(|node| {
+                        built_nodes[node]
+                            .assert_input_node()
+                            .map_or(false, |node| node.holds_frames_for_consumers())
+                    });
+                    let control = match holding.and_then(|node| metrics.get(node)) {
+                        Some(node) => SinkControl::fixed(format!(
+                            "it is fed through {} ({}), which keeps every frame until all of \
+                             its consumers pulled it",
+                            node.name, node.node_type
+                        )),
+                        None => SinkControl::default(),
+                    };
+                    (*id, Arc::new(control))
+                })
+                .collect();
+            Ok(ProcessingGraph {
+                nodes: built_nodes,
+                sinks,
+                sink_controls,
+                parameters,
+                warnings,
+                metrics,
+            })
+        }
+    }
+}
+
+impl ProcessingGraphBuilder<String> {
+    /// Adds the nodes of an instance of `subgraph`, named `<name>/<node>`.
+    /// `config` is the node using the sub-graph, its parameters and the
+    /// `vars` are passed to the template of the sub-graph. Other nodes refer
+    /// to the outputs as `<name` and `<name.<output>`.
+    pub fn add_subgraph(
+        &mut self,
+        name: String,
+        subgraph: &Subgraph,
+        config: ProcessingNodeConfig<String>,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        for input in config.inputs.keys() {
+            if !subgraph.inputs.contains(input) {
+                bail!("sub-graph {} has no input {input}", subgraph.name);
+            }
+        }
+        let (nodes, vars) = subgraph
+            .render(&config.parameters, vars)
+            .with_context(|| format!("couldn't instantiate sub-graph {name}"))?;
+
+        for (inner, node) in nodes {
+            let mut node: ProcessingNodeConfig<String> = node.into();
+            for input in node.inputs.values_mut() {
+                *input = if subgraph.inputs.contains(input) {
+                    config.inputs.get(input).cloned().ok_or_else(|| {
+                        anyhow!("input {input} of sub-graph {name} is not connected")
+                    })?
+                } else {
+                    format!("{name}/{input}")
+                };
+            }
+            let id = format!("{name}/{inner}");
+            match subgraph.subgraphs.get(&node.name) {
+                Some(nested) => self.add_subgraph(id, nested, node, &vars)?,
+                None => {
+                    self.add(id, node)?;
                 }
             }
+        }
 
-            Ok(ProcessingGraph { nodes: built_nodes, sinks })
+        for (output, inner) in &subgraph.outputs {
+            self.add_alias(format!("{name}.{output}"), format!("{name}/{inner}"))?;
         }
+        if let Some(output) = subgraph.default_output() {
+            self.add_alias(name.clone(), format!("{name}/{}", subgraph.outputs[output]))?;
+        }
+        Ok(())
     }
 }
 
 pub struct ProcessingGraph {
     nodes: HashMap<NodeID, Node>,
     sinks: Vec<NodeID>,
+    sink_controls: HashMap<NodeID, Arc<SinkControl>>,
+    // the current values of the parameters, rendered as strings
+    parameters: HashMap<NodeID, Mutex<BTreeMap<String, String>>>,
+    warnings: Vec<GraphWarning>,
+    metrics: Arc<GraphMetrics>,
 }
 
 impl ProcessingGraph {
@@ -258,11 +820,36 @@ impl ProcessingGraph {
                             let progress_update_cb = progress_update_cb.clone();
                             let ctx = ctx.clone();
                             let sink = self.nodes.get(&id).unwrap().assert_sink()?;
+                            let control = self.sink_controls[&id].clone();
                             Ok(async move {
-                                anyhow::Result::<_, anyhow::Error>::Ok(
-                                    sink.run(&*ctx, std::sync::Arc::new(progress_update_cb))
-                                        .await?,
-                                )
+                                loop {
+                                    control.armed(&ctx).await;
+                                    if ctx.is_cancelled() {
+                                        return Ok(());
+                                    }
+                                    let sink_ctx = control.start(&ctx);
+                                    let progress_update_cb = {
+                                        let control = control.clone();
+                                        let progress_update_cb = progress_update_cb.clone();
+                                        Arc::new(move |update: ProgressUpdate| {
+                                            control.record_progress(update);
+                                            progress_update_cb(update)
+                                        })
+                                    };
+                                    let result = sink.run(&sink_ctx, progress_update_cb).await;
+                                    let finalized = sink.finalize(&sink_ctx).await;
+                                    let result = result.and(finalized);
+                                    // only the errors of this run, the sink can
+                                    // have failed before it was re-armed
+                                    let failure = sink_ctx.errors().failure().is_some();
+                                    if failure {
+                                        // the puller only cancelled the context of this sink
+                                        ctx.cancel();
+                                    }
+                                    if !control.stop(result.is_err() || failure, &sink_ctx, &ctx) {
+                                        return result;
+                                    }
+                                }
                             })
                         })
                         .collect::<Result<futures::stream::FuturesUnordered<_>>>()?
@@ -274,10 +861,538 @@ impl ProcessingGraph {
             for r in res {
                 r?
             }
+            // sinks using `pull_ordered` can't return the error of the puller themselves
+            if let Some(failure) = ctx.errors().failure() {
+                return Err(anyhow::anyhow!(
+                    "couldn't pull frame {}: {}",
+                    failure.frame,
+                    failure.error
+                ));
+            }
 
             Ok(())
         }
     }
 
     pub fn get_node(&self, id: NodeID) -> &Node { &self.nodes[&id] }
+
+    pub fn sinks(&self) -> &[NodeID] { &self.sinks }
+
+    /// Arms and disarms the sink `id` while the graph runs.
+    pub fn sink_control(&self, id: NodeID) -> Option<&Arc<SinkControl>> {
+        self.sink_controls.get(&id)
+    }
+
+    /// The current values of the parameters of a node, including the defaults
+    /// and the live updates.
+    pub fn parameters(&self, id: NodeID) -> BTreeMap<String, String> {
+        self.parameters.get(&id).map(|values| values.lock().unwrap().clone()).unwrap_or_default()
+    }
+
+    /// Changes parameters of a node while the graph is running. Only the
+    /// parameters the node declared as live can be changed, the values are
+    /// parsed according to their declared type. The node uses the new values
+    /// starting with the next frame it processes.
+    pub fn update_parameters(&self, id: NodeID, values: &HashMap<String, String>) -> Result<()> {
+        let (node, metrics) = self
+            .nodes
+            .get(&id)
+            .zip(self.metrics.get(id))
+            .ok_or_else(|| anyhow!("there is no node with id {id:?}"))?;
+        let descriptor = list_available_nodes()
+            .remove(&metrics.node_type)
+            .ok_or_else(|| anyhow!("no node named {} found", metrics.node_type))?
+            .parameters_descriptor;
+
+        let mut parameters = HashMap::new();
+        let mut rendered = BTreeMap::new();
+        for (key, value) in values {
+            let parameter_type = descriptor
+                .0
+                .get(key)
+                .ok_or_else(|| anyhow!("node {} has no parameter {key}", metrics.name))?;
+            if !descriptor.is_live(key) {
+                bail!("parameter {key} of node {} can't be changed while running", metrics.name);
+            }
+            let value = parameter_type
+                .get_parameter_type()
+                .parse(value)
+                .with_context(|| format!("invalid value for parameter {key}"))?;
+            rendered.insert(key.clone(), value.to_string());
+            parameters.insert(key.clone(), value);
+        }
+
+        node.update_parameters(Parameters::new(parameters))
+            .with_context(|| format!("couldn't update the parameters of node {}", metrics.name))?;
+        if let Some(values) = self.parameters.get(&id) {
+            values.lock().unwrap().extend(rendered);
+        }
+        Ok(())
+    }
+
+    /// Problems of the graph that don't prevent it from running.
+    pub fn warnings(&self) -> &[GraphWarning] { &self.warnings }
+
+    /// Runtime statistics of the nodes, they are updated while the graph runs.
+    pub fn metrics(&self) -> Arc<GraphMetrics> { self.metrics.clone() }
+
+    /// The statistics of the frame cache of a node, for `LruCache` and the
+    /// readers with `cache-frames`.
+    pub fn cache_stats(&self, id: NodeID) -> Option<LruStats> {
+        self.nodes.get(&id)?.assert_input_node().ok()?.cache_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GraphFormat, ProcessingGraph, ProcessingGraphBuilder, SerdeNodeConfig};
+    use crate::pipeline_processing::processing_context::ProcessingContext;
+    use anyhow::Result;
+    use indoc::indoc;
+    use std::collections::HashMap;
+
+    fn builder_from_yaml(yaml: &str) -> Result<ProcessingGraphBuilder<String>> {
+        let nodes: HashMap<String, SerdeNodeConfig> = serde_yaml::from_str(yaml)?;
+        let mut builder = ProcessingGraphBuilder::new();
+        for (name, node) in nodes {
+            builder.add(name, node.into())?;
+        }
+        Ok(builder)
+    }
+
+    fn build_from_yaml(yaml: &str) -> Result<ProcessingGraph> {
+        builder_from_yaml(yaml)?.build(&ProcessingContext::default())
+    }
+
+    #[test]
+    fn test_type_check_accepts_compatible_pipeline() {
+        build_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+            convert:
+              type: BitDepthConverter
+              input: <source
+            sink:
+              type: BenchmarkSink
+              input: <convert
+            "
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_type_check_rejects_incompatible_connection() {
+        let error = build_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+            writer:
+              type: FfmpegWriter
+              input: <source
+              output: out.mp4
+            "
+        ))
+        .err()
+        .unwrap();
+        let message = format!("{error:#}");
+        assert!(message.contains(r#""source" -> "writer".input"#), "{message}");
+        assert!(message.contains("bit_depth=8"), "{message}");
+    }
+
+    #[test]
+    fn test_type_check_rejects_split_of_frame() {
+        let error = build_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 8
+            split:
+              type: Split
+              input: <source
+              element: 0
+            sink:
+              type: BenchmarkSink
+              input: <split
+            "
+        ))
+        .err()
+        .unwrap();
+        assert!(format!("{error:#}").contains("expected a list of payloads"));
+    }
+
+    #[test]
+    fn test_render_dot() {
+        let builder = builder_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 32
+              uint-bits: 12
+            writer:
+              type: RawBlobWriter
+              input: <source
+              path: out.raw
+            "
+        ))
+        .unwrap();
+        let dot = builder.render(GraphFormat::Dot).unwrap();
+
+        assert!(dot.starts_with("digraph pipeline {"), "{dot}");
+        assert!(dot.contains("\\\"writer\\\": RawBlobWriter"), "{dot}");
+        // defaults are filled in
+        assert!(dot.contains("priority = 0"), "{dot}");
+        assert!(dot.contains("[label=\"input: 64x32 bayer RGGB uint12 @ 24fps\"]"), "{dot}");
+    }
+
+    #[test]
+    fn test_render_propagates_interpretation() {
+        let builder = builder_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 32
+              uint-bits: 12
+            converter:
+              type: BitDepthConverter
+              input: <source
+            writer:
+              type: RawBlobWriter
+              input: <converter
+              path: out.raw
+            "
+        ))
+        .unwrap();
+        let mermaid = builder.render(GraphFormat::Mermaid).unwrap();
+
+        assert!(mermaid.contains("|\"input: 64x32 bayer RGGB uint12 @ 24fps\"|"), "{mermaid}");
+        assert!(mermaid.contains("|\"input: 64x32 bayer RGGB uint8 @ 24fps\"|"), "{mermaid}");
+    }
+
+    #[test]
+    fn test_unknown_input() {
+        let error = build_from_yaml(indoc!(
+            "
+            debayer:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 8
+            sink:
+              type: BenchmarkSink
+              input: <debayr
+            "
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "input input of node \"sink\" refers to the unknown node \"debayr\", did you mean \"debayer\"?"
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let error = build_from_yaml(indoc!(
+            "
+            a:
+              type: BitDepthConverter
+              input: <c
+            b:
+              type: BitDepthConverter
+              input: <a
+            c:
+              type: BitDepthConverter
+              input: <b
+            sink:
+              type: BenchmarkSink
+              input: <c
+            "
+        ))
+        .err()
+        .unwrap();
+        let message = error.to_string();
+        assert!(message.starts_with("the processing graph contains a cycle: "), "{message}");
+        // the cycle can be entered at any of its nodes
+        assert!(
+            [
+                r#""a" -> "c" -> "b" -> "a""#,
+                r#""b" -> "a" -> "c" -> "b""#,
+                r#""c" -> "b" -> "a" -> "c""#
+            ]
+            .iter()
+            .any(|path| message.ends_with(path)),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_unused_node_warning() {
+        let graph = build_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+            unused:
+              type: BitDepthConverter
+              input: <source
+            sink:
+              type: BenchmarkSink
+              input: <source
+            "
+        ))
+        .unwrap();
+        let warnings = graph.warnings().iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(warnings, ["the output of node \"unused\" is not used by any sink"]);
+    }
+
+    #[test]
+    fn test_update_parameters_rejects_non_live_parameters() {
+        let builder = builder_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+            sink:
+              type: BenchmarkSink
+              input: <source
+            "
+        ))
+        .unwrap();
+        let source = builder.node_ids()["source"];
+        let graph = builder.build(&ProcessingContext::default()).unwrap();
+
+        let update = |key: &str, value: &str| {
+            let values = HashMap::from([(key.to_string(), value.to_string())]);
+            format!("{:#}", graph.update_parameters(source, &values).err().unwrap())
+        };
+        assert!(update("width", "128").contains("can't be changed while running"));
+        assert!(update("widht", "128").contains("has no parameter widht"));
+    }
+
+    #[test]
+    fn test_unknown_parameter() {
+        let yaml = indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+            cache:
+              type: Cache
+              input: <source
+              cache_frames: true
+            sink:
+              type: BenchmarkSink
+              input: <cache
+            "
+        );
+        let error = build_from_yaml(yaml).err().unwrap();
+        let message = format!("{error:#}");
+        assert!(message.contains("couldn't create node \"cache\""), "{message}");
+        assert!(message.contains("Cache has no parameter cache_frames"), "{message}");
+
+        let mut builder = builder_from_yaml(yaml).unwrap();
+        builder.allow_unknown_parameters(true);
+        builder.build(&ProcessingContext::default()).unwrap();
+
+        // reported instead of the error of the node about the missing uint-bits
+        let error = build_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint_bits: 12
+            sink:
+              type: BenchmarkSink
+              input: <source
+            "
+        ))
+        .err()
+        .unwrap();
+        let message = format!("{error:#}");
+        assert!(message.contains("no parameter uint_bits, did you mean uint-bits?"), "{message}");
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let error = build_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+              bayer: RGGB
+            sink:
+              type: BenchmarkSink
+              input: <source
+            "
+        ))
+        .err()
+        .unwrap();
+        let message = format!("{error:#}");
+        assert!(message.contains("invalid value for parameter bayer"), "{message}");
+        assert!(message.contains("RGGB is not one of RGBG, BGRG, GBGR, GRGB"), "{message}");
+    }
+
+    #[test]
+    fn test_named_outputs() {
+        let yaml = |output: &str| {
+            format!(
+                indoc!(
+                    "
+                    source:
+                      type: NullFrameSource
+                      width: 64
+                      height: 64
+                      uint-bits: 8
+                    hist:
+                      type: Histogram
+                      input: <source
+                    histogram_sink:
+                      type: BenchmarkSink
+                      input: <hist
+                    image_sink:
+                      type: BenchmarkSink
+                      input: <hist.{}
+                    "
+                ),
+                output
+            )
+        };
+
+        let builder = builder_from_yaml(&yaml("image")).unwrap();
+        builder.validate().unwrap();
+        let dot = builder.render(GraphFormat::Dot).unwrap();
+        assert!(dot.contains("label=\"image -> input\""), "{dot}");
+
+        let error = builder_from_yaml(&yaml("imag")).unwrap().validate().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "input input of node \"image_sink\" refers to the output imag of \"hist\", which \
+             has no such output, did you mean image?"
+        );
+    }
+
+    #[test]
+    fn test_upstream_feeding_two_inputs() {
+        let builder = builder_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 12
+            first:
+              type: Range
+              input: <source
+              end: 10
+            concat:
+              type: Concat
+              inputs: [<first, <first]
+            sink:
+              type: BenchmarkSink
+              input: <concat
+            "
+        ))
+        .unwrap();
+        let concat = builder.node_ids()["concat"];
+        let graph = builder.build(&ProcessingContext::default()).unwrap();
+        let caps = graph.get_node(concat).assert_input_node().unwrap().get_caps();
+        assert_eq!(caps.frame_count, Some(20));
+    }
+
+    #[test]
+    fn test_named_output_behind_cache() {
+        // both outputs of the histogram share one pull of its input, a second
+        // one would pull the frames of the cache more often than it expects
+        let context = ProcessingContext::default();
+        let builder = builder_from_yaml(indoc!(
+            "
+            source:
+              type: NullFrameSource
+              width: 64
+              height: 64
+              uint-bits: 8
+            first:
+              type: Range
+              input: <source
+              end: 4
+            cache:
+              type: Cache
+              input: <first
+            hist:
+              type: Histogram
+              input: <cache
+            histogram_sink:
+              type: BenchmarkSink
+              input: <hist
+            image_sink:
+              type: BenchmarkSink
+              input: <hist.image
+            "
+        ))
+        .unwrap();
+        let graph = builder.build(&context).unwrap();
+        graph.run(context, |_| {}).unwrap();
+    }
+
+    #[test]
+    fn test_list_of_inputs() {
+        let yaml = |inputs: &str| {
+            format!(
+                indoc!(
+                    "
+                    a:
+                      type: NullFrameSource
+                      width: 64
+                      height: 64
+                      uint-bits: 12
+                    b:
+                      type: NullFrameSource
+                      width: 64
+                      height: 64
+                      uint-bits: 12
+                    first:
+                      type: Range
+                      input: <a
+                      end: 10
+                    concat:
+                      type: Concat
+                      inputs: {}
+                    sink:
+                      type: BenchmarkSink
+                      input: <concat
+                    "
+                ),
+                inputs
+            )
+        };
+
+        let builder = builder_from_yaml(&yaml("[<first, <b]")).unwrap();
+        let dot = builder.render(GraphFormat::Dot).unwrap();
+        assert!(dot.contains("label=\"inputs[1]: 64x64"), "{dot}");
+        builder.build(&ProcessingContext::default()).unwrap();
+
+        let error = build_from_yaml(&yaml("[<b, <first]")).err().unwrap();
+        let message = format!("{error:#}");
+        assert!(message.contains("input 0 doesn't know how many frames it has"), "{message}");
+    }
 }
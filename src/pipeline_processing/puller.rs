@@ -1,81 +1,405 @@
-// TODO(robin): make pullers pull the actual amount specified, requesting more
-// if there are errors
 use crate::pipeline_processing::{
-    node::{EOFError, InputProcessingNode, ProgressUpdate, Request},
+    node::{Drop, DroppedError, EOFError, InputProcessingNode, ProgressUpdate, Request},
     payload::Payload,
     processing_context::{Priority, ProcessingContext},
 };
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use bytemuck::Contiguous;
 use futures::{
     stream::{FuturesOrdered, FuturesUnordered},
     StreamExt,
 };
 use std::{
+    fmt::Write,
+    ops::Range,
+    str::FromStr,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
+        Mutex,
     },
     u64,
 };
 
+/// What a sink does with frames that could not be pulled. It is set with the
+/// `on-error` parameter of the sinks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the pipeline and fail.
+    Fail,
+    /// Leave the frame out of the output.
+    Skip,
+    /// Pull the frame up to n more times and skip it if it still fails. Only
+    /// inputs with random access can be retried.
+    Retry(u32),
+    /// Output the last good frame again, so that the frame count and the
+    /// timing of the output are preserved. Sinks that pull unordered repeat the
+    /// good frame that finished last, which can also be a later one.
+    RepeatLast,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            "repeat-last" => Ok(Self::RepeatLast),
+            _ => match s.strip_prefix("retry:") {
+                Some(retries) => Ok(Self::Retry(
+                    retries
+                        .parse()
+                        .with_context(|| format!("couldn't parse the number of retries in {s:?}"))?,
+                )),
+                None => bail!(
+                    "unknown error policy {s:?}, expected one of fail, skip, retry:<n> or repeat-last"
+                ),
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorOutcome {
+    Failed,
+    Skipped,
+    Repeated,
+    Recovered { retries: u32 },
+}
+
+impl std::fmt::Display for ErrorOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed => write!(f, "failed"),
+            Self::Skipped => write!(f, "skipped"),
+            Self::Repeated => write!(f, "repeated the previous frame"),
+            Self::Recovered { retries } => write!(f, "recovered after {retries} retries"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ErrorRecord {
+    pub frame: u64,
+    pub error: String,
+    pub outcome: ErrorOutcome,
+}
+
+/// The errors the pullers ran into and what they did about them. It lives in
+/// the `ProcessingContext` so the errors of all sinks can be summarized once
+/// the pipeline finished.
+#[derive(Default)]
+pub struct ErrorLog {
+    records: Mutex<Vec<ErrorRecord>>,
+    parent: Option<Arc<ErrorLog>>,
+}
+
+impl ErrorLog {
+    /// A log of its own, for example for one run of a sink. Its records are
+    /// also added to this log.
+    pub(crate) fn child(self: &Arc<Self>) -> Self {
+        Self { records: Default::default(), parent: Some(self.clone()) }
+    }
+
+    fn record(&self, frame: u64, error: &anyhow::Error, outcome: ErrorOutcome) {
+        self.records.lock().unwrap().push(ErrorRecord {
+            frame,
+            error: format!("{error:#}"),
+            outcome,
+        });
+        if let Some(parent) = &self.parent {
+            parent.record(frame, error, outcome);
+        }
+    }
+
+    pub fn records(&self) -> Vec<ErrorRecord> { self.records.lock().unwrap().clone() }
+
+    /// The error that made a sink with the `fail` policy stop.
+    pub fn failure(&self) -> Option<ErrorRecord> {
+        self.records.lock().unwrap().iter().find(|r| r.outcome == ErrorOutcome::Failed).cloned()
+    }
+
+    /// Returns `None` if no errors occurred.
+    pub fn summary(&self) -> Option<String> {
+        const SHOWN_ERRORS: usize = 10;
+
+        let records = self.records.lock().unwrap();
+        if records.is_empty() {
+            return None;
+        }
+        let count = |matches: fn(&ErrorOutcome) -> bool| {
+            records.iter().filter(|record| matches(&record.outcome)).count()
+        };
+        let mut summary = format!(
+            "errors occurred in {} frames: {} failed, {} skipped, {} repeated, {} recovered\n",
+            records.len(),
+            count(|o| *o == ErrorOutcome::Failed),
+            count(|o| *o == ErrorOutcome::Skipped),
+            count(|o| *o == ErrorOutcome::Repeated),
+            count(|o| matches!(o, ErrorOutcome::Recovered { .. })),
+        );
+        for record in records.iter().take(SHOWN_ERRORS) {
+            writeln!(summary, "  frame {} {}: {}", record.frame, record.outcome, record.error)
+                .unwrap();
+        }
+        if records.len() > SHOWN_ERRORS {
+            writeln!(summary, "  ... and {} more", records.len() - SHOWN_ERRORS).unwrap();
+        }
+        Some(summary)
+    }
+}
+
+// errors can reach the puller wrapped in an `Arc` if they were shared by a node
+fn is_caused_by<E: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static>(
+    error: &anyhow::Error,
+) -> bool {
+    error.downcast_ref::<E>().is_some()
+        || error
+            .downcast_ref::<Arc<anyhow::Error>>()
+            .map_or(false, |error| error.downcast_ref::<E>().is_some())
+}
+
+async fn pull_with_retries(
+    context: &ProcessingContext,
+    input: &InputProcessingNode,
+    request: Request,
+    on_error: ErrorPolicy,
+) -> Result<Payload> {
+    // inputs without random access have moved on and can't produce the frame again
+    let retries = match on_error {
+        ErrorPolicy::Retry(retries) if input.get_caps().random_access => retries,
+        _ => 0,
+    };
+    let frame = request.frame_number();
+    let mut retry = 0;
+    let mut last_error = None;
+    loop {
+        match input.pull(request.clone()).await {
+            Ok(payload) => {
+                if let Some(error) = last_error {
+                    context.errors().record(
+                        frame,
+                        &error,
+                        ErrorOutcome::Recovered { retries: retry },
+                    );
+                }
+                return Ok(payload);
+            }
+            Err(e)
+                if retry < retries
+                    && !is_caused_by::<DroppedError>(&e)
+                    && !is_caused_by::<EOFError>(&e) =>
+            {
+                retry += 1;
+                eprintln!("error pulling frame {frame}: {e:#}, retrying ({retry}/{retries})");
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// How many frames a puller pulls.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameCount {
+    /// All frames of the input.
+    All,
+    /// The frame indices `0..n`. Frames that fail are missing from the output.
+    Indices(u64),
+    /// Exactly `frames` frames. Further frame indices are requested until
+    /// enough frames were delivered, the puller gives up after more than
+    /// `max_consecutive_failures` failed frames in a row.
+    Exact { frames: u64, max_consecutive_failures: u64 },
+}
+
+impl FrameCount {
+    fn frames_to_request(
+        self,
+        context: &ProcessingContext,
+        input: &InputProcessingNode,
+    ) -> Range<u64> {
+        let limit = match self {
+            Self::Indices(n) => Some(n),
+            Self::All | Self::Exact { .. } => None,
+        };
+        let end = match (limit, input.get_caps().frame_count) {
+            (None, None) => u64::MAX_VALUE,
+            (None, Some(n)) => n,
+            (Some(n), None) => n,
+            (Some(n), Some(m)) => n.min(m),
+        };
+        context.first_frame().min(end)..end
+    }
+
+    // whether another frame should be requested in addition to the ones in flight
+    fn wants_more(self, delivered: u64, in_flight: usize) -> bool {
+        match self {
+            Self::Exact { frames, .. } => delivered + (in_flight as u64) < frames,
+            Self::All | Self::Indices(_) => true,
+        }
+    }
+
+    fn check_failures(self, context: &ProcessingContext, frame: u64, failures: u64) -> Result<()> {
+        match self {
+            Self::Exact { max_consecutive_failures, .. } if failures > max_consecutive_failures => {
+                let error = anyhow!("giving up after {failures} consecutive failed frames");
+                context.errors().record(frame, &error, ErrorOutcome::Failed);
+                context.cancel();
+                Err(error)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn warn_if_incomplete(self, context: &ProcessingContext, delivered: u64) {
+        if let Self::Exact { frames, .. } = self {
+            if delivered < frames && context.errors().failure().is_none() {
+                eprintln!("warning: the input ended after {delivered} of {frames} frames");
+            }
+        }
+    }
+}
+
+enum Handled {
+    Deliver(Payload),
+    Skip,
+    Dropped,
+    EndOfStream,
+    Fail(anyhow::Error),
+}
+
+fn handle_error(
+    context: &ProcessingContext,
+    on_error: ErrorPolicy,
+    frame: u64,
+    error: anyhow::Error,
+    last_good: Option<&Payload>,
+) -> Handled {
+    if is_caused_by::<DroppedError>(&error) {
+        return Handled::Dropped;
+    }
+    // TODO(robin): clean up into own trait?
+    eprintln!("error pulling frame {frame}: {error:#}");
+    if is_caused_by::<EOFError>(&error) {
+        eprintln!("end of file, exiting");
+        return Handled::EndOfStream;
+    }
+
+    match (on_error, last_good) {
+        (ErrorPolicy::Fail, _) => {
+            context.errors().record(frame, &error, ErrorOutcome::Failed);
+            context.cancel();
+            Handled::Fail(error)
+        }
+        (ErrorPolicy::RepeatLast, Some(payload)) => {
+            context.errors().record(frame, &error, ErrorOutcome::Repeated);
+            Handled::Deliver(payload.clone())
+        }
+        _ => {
+            context.errors().record(frame, &error, ErrorOutcome::Skipped);
+            Handled::Skip
+        }
+    }
+}
+
 pub async fn pull_unordered(
     context: &ProcessingContext,
     output_priority: u8,
     progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
     input: InputProcessingNode,
-    number_of_frames: Option<u64>,
+    frame_count: FrameCount,
+    on_error: ErrorPolicy,
     on_payload: impl Fn(Payload, u64) -> Result<()> + Send + Sync + Clone + 'static,
 ) -> Result<()> {
-    let mut range = match (number_of_frames, input.get_caps().frame_count) {
-        (None, None) => 0..u64::MAX_VALUE,
-        (None, Some(n)) => 0..n,
-        (Some(n), None) => 0..n,
-        (Some(n), Some(m)) => 0..n.min(m),
-    };
+    let mut range = frame_count.frames_to_request(context, &input);
 
-    let total_frames = if range.end == u64::MAX_VALUE { None } else { Some(range.end as _) };
+    let total_frames = match frame_count {
+        FrameCount::Exact { frames, .. } => Some(frames),
+        _ if range.end == u64::MAX_VALUE => None,
+        _ => Some(range.end),
+    };
 
     let latest_frame = Arc::new(AtomicU64::new(0));
     let should_stop = Arc::new(AtomicBool::new(false));
+    // the frames finish in any order, so this is the good frame that finished
+    // last and not necessarily the one before a failed frame
+    let last_good: Arc<Mutex<Option<Payload>>> = Default::default();
+    let delivered = Arc::new(AtomicU64::new(0));
+    let consecutive_failures = Arc::new(AtomicU64::new(0));
     let mut futures_unordered = FuturesUnordered::new();
 
     loop {
-        if range.is_empty() && futures_unordered.is_empty() {
+        if context.is_cancelled() {
+            // stop requesting new frames, the ones in flight are still processed
+            range.start = range.end;
+        }
+        let can_request = !range.is_empty()
+            && frame_count.wants_more(delivered.load(Ordering::Relaxed), futures_unordered.len());
+        if !can_request && futures_unordered.is_empty() {
             break;
         }
-        if range.is_empty() || futures_unordered.len() >= context.num_threads() {
+        if !can_request || futures_unordered.len() >= context.num_threads() {
             if let Some(result) = futures_unordered.next().await {
                 result?;
             }
         }
+        if !frame_count.wants_more(delivered.load(Ordering::Relaxed), futures_unordered.len()) {
+            continue;
+        }
         if let Some(frame) = range.next() {
             let input = input.clone_for_same_puller();
             let on_payload = on_payload.clone();
             let progress_callback = progress_callback.clone();
             let latest_frame = latest_frame.clone();
             let should_stop_fut = should_stop.clone();
+            let last_good = last_good.clone();
+            let delivered = delivered.clone();
+            let consecutive_failures = consecutive_failures.clone();
+            let context_fut = context.clone();
             let res = futures_unordered.push(context.spawn(
                 Priority::new(output_priority, frame),
                 async move {
-                    match input.pull(Request::new(output_priority, frame)).await {
-                        Ok(pulled) => on_payload(pulled, frame as _)?,
+                    let request = Request::new(output_priority, frame);
+                    let payload = match pull_with_retries(&context_fut, &input, request, on_error)
+                        .await
+                    {
+                        Ok(pulled) => {
+                            if on_error == ErrorPolicy::RepeatLast {
+                                *last_good.lock().unwrap() = Some(pulled.clone());
+                            }
+                            Some(pulled)
+                        }
                         Err(e) => {
-                            // TODO(robin): clean up into own trait?
-                            eprintln!("error pulling frame {frame}: {e:#}");
-                            if let Some(&EOFError) = e.downcast_ref::<EOFError>() {
-                                eprintln!("end of file, exiting");
-                                should_stop_fut.store(true, Ordering::Relaxed);
-                            } else if let Some(e) = e.downcast_ref::<Arc<anyhow::Error>>() {
-                                if let Some(&EOFError) = e.downcast_ref::<EOFError>() {
-                                    eprintln!("end of file, exiting");
+                            let last_good = last_good.lock().unwrap().clone();
+                            match handle_error(&context_fut, on_error, frame, e, last_good.as_ref())
+                            {
+                                Handled::Deliver(payload) => Some(payload),
+                                Handled::Skip => {
+                                    let failures =
+                                        consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                                    frame_count.check_failures(&context_fut, frame, failures)?;
+                                    None
+                                }
+                                Handled::Dropped => None,
+                                Handled::EndOfStream => {
                                     should_stop_fut.store(true, Ordering::Relaxed);
+                                    None
+                                }
+                                Handled::Fail(e) => {
+                                    return Err(e.context(format!("couldn't pull frame {frame}")))
                                 }
                             }
                         }
+                    };
+                    if let Some(payload) = payload {
+                        on_payload(payload, frame as _)?;
+                        consecutive_failures.store(0, Ordering::Relaxed);
+                        delivered.fetch_add(1, Ordering::Relaxed);
                     }
 
-                    let latest_frame = latest_frame.fetch_max(frame as _, Ordering::Relaxed);
+                    let latest_frame =
+                        latest_frame.fetch_max(frame as _, Ordering::Relaxed).max(frame as _);
                     progress_callback(ProgressUpdate { latest_frame, total_frames });
 
                     Ok::<(), anyhow::Error>(())
@@ -90,28 +414,78 @@ pub async fn pull_unordered(
         }
     }
 
+    frame_count.warn_if_incomplete(context, delivered.load(Ordering::Relaxed));
     Ok(())
 }
 
-// TODO(robin): abort the thread when we want to stop
+/// Pulls the frames in order on a separate thread. With the `Fail` policy the
+/// channel is closed at the first error, the error can then be found in
+/// `ProcessingContext::errors`.
 pub fn pull_ordered(
     context: &ProcessingContext,
     output_priority: u8,
     progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
     input: InputProcessingNode,
-    number_of_frames: Option<u64>,
+    frame_count: FrameCount,
+    on_error: ErrorPolicy,
 ) -> flume::Receiver<Payload> {
-    let mut range = match (number_of_frames, input.get_caps().frame_count) {
-        (None, None) => 0..u64::MAX_VALUE,
-        (None, Some(n)) => 0..n,
-        (Some(n), None) => 0..n,
-        (Some(n), Some(m)) => 0..n.min(m),
-    };
+    pull_ordered_impl(
+        context,
+        output_priority,
+        progress_callback,
+        input,
+        frame_count,
+        on_error,
+        false,
+    )
+}
+
+/// Like `pull_ordered`, but for sinks that show a live preview and would rather
+/// skip frames than fall behind. While the receiver is not keeping up, new
+/// frames are requested with the `Drop` hint and finished frames that do not
+/// fit into the channel are discarded.
+pub fn pull_live(
+    context: &ProcessingContext,
+    output_priority: u8,
+    progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
+    input: InputProcessingNode,
+    frame_count: FrameCount,
+    on_error: ErrorPolicy,
+) -> flume::Receiver<Payload> {
+    pull_ordered_impl(
+        context,
+        output_priority,
+        progress_callback,
+        input,
+        frame_count,
+        on_error,
+        true,
+    )
+}
 
-    let total_frames = if range.end == u64::MAX_VALUE { None } else { Some(range.end as _) };
+// TODO(robin): abort the thread when we want to stop
+fn pull_ordered_impl(
+    context: &ProcessingContext,
+    output_priority: u8,
+    progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
+    input: InputProcessingNode,
+    frame_count: FrameCount,
+    on_error: ErrorPolicy,
+    drop_late: bool,
+) -> flume::Receiver<Payload> {
+    let mut range = frame_count.frames_to_request(context, &input);
+
+    let total_frames = match frame_count {
+        FrameCount::Exact { frames, .. } => Some(frames),
+        _ if range.end == u64::MAX_VALUE => None,
+        _ => Some(range.end),
+    };
 
     let latest_frame = Arc::new(AtomicU64::new(0));
     let mut futures_ordered = FuturesOrdered::new();
+    let mut last_good = None;
+    let mut delivered = 0;
+    let mut consecutive_failures = 0;
 
     let (tx, rx) = flume::bounded(context.num_threads());
 
@@ -119,40 +493,76 @@ pub fn pull_ordered(
     std::thread::spawn(move || {
         context.block_on(async {
             loop {
-                if range.is_empty() && futures_ordered.is_empty() {
+                if context.is_cancelled() {
+                    // stop requesting new frames, the ones in flight are still delivered
+                    range.start = range.end;
+                }
+                let can_request =
+                    !range.is_empty() && frame_count.wants_more(delivered, futures_ordered.len());
+                if !can_request && futures_ordered.is_empty() {
                     break;
                 }
-                if range.is_empty() || futures_ordered.len() >= context.num_threads() {
+                if !can_request || futures_ordered.len() >= context.num_threads() {
                     if let Some(input) = futures_ordered.next().await {
-                        let input: (Result<_, anyhow::Error>, _) = input;
-                        match input {
-                            (Ok(input), _) => tx.send_async(input).await.unwrap(),
-                            (Err(e), frame) => {
-                                // TODO(robin): clean up into own trait?
-                                eprintln!("error pulling frame {frame}: {e:#}");
-                                if let Some(&EOFError) = e.downcast_ref::<EOFError>() {
-                                    eprintln!("end of file, exiting");
-                                    break;
-                                } else if let Some(e) = e.downcast_ref::<Arc<anyhow::Error>>() {
-                                    if let Some(&EOFError) = e.downcast_ref::<EOFError>() {
-                                        eprintln!("end of file, exiting");
-                                        break;
+                        let (input, frame): (Result<_, anyhow::Error>, _) = input;
+                        let payload = match input {
+                            Ok(payload) => payload,
+                            Err(e) => {
+                                match handle_error(&context, on_error, frame, e, last_good.as_ref())
+                                {
+                                    Handled::Deliver(payload) => payload,
+                                    Handled::Skip => {
+                                        consecutive_failures += 1;
+                                        if frame_count
+                                            .check_failures(&context, frame, consecutive_failures)
+                                            .is_err()
+                                        {
+                                            break;
+                                        }
+                                        continue;
                                     }
+                                    Handled::Dropped => continue,
+                                    Handled::EndOfStream | Handled::Fail(_) => break,
                                 }
                             }
+                        };
+                        consecutive_failures = 0;
+                        if on_error == ErrorPolicy::RepeatLast {
+                            last_good = Some(payload.clone());
+                        }
+                        if drop_late {
+                            match tx.try_send(payload) {
+                                Ok(()) => delivered += 1,
+                                // discarded, so it doesn't count towards the frames to deliver
+                                Err(flume::TrySendError::Full(_)) => {}
+                                Err(flume::TrySendError::Disconnected(_)) => break,
+                            }
+                        } else {
+                            tx.send_async(payload).await.unwrap();
+                            delivered += 1;
                         }
                     }
                 }
+                if !frame_count.wants_more(delivered, futures_ordered.len()) {
+                    continue;
+                }
                 if let Some(frame) = range.next() {
                     let input = input.clone_for_same_puller();
                     let progress_callback = progress_callback.clone();
                     let latest_frame = latest_frame.clone();
+                    let context_fut = context.clone();
+                    let mut request = Request::new(output_priority, frame);
+                    if drop_late && tx.is_full() {
+                        request = request.with_extra(Drop);
+                    }
                     futures_ordered.push_back(context.spawn(
                         Priority::new(output_priority, frame),
                         async move {
-                            let input = input.pull(Request::new(output_priority, frame)).await;
-                            let latest_frame =
-                                latest_frame.fetch_max(frame as _, Ordering::Relaxed);
+                            let input =
+                                pull_with_retries(&context_fut, &input, request, on_error).await;
+                            let latest_frame = latest_frame
+                                .fetch_max(frame as _, Ordering::Relaxed)
+                                .max(frame as _);
                             progress_callback(ProgressUpdate { latest_frame, total_frames });
 
                             (input, frame as u64)
@@ -160,8 +570,151 @@ pub fn pull_ordered(
                     ));
                 }
             }
+            frame_count.warn_if_incomplete(&context, delivered);
         })
     });
 
     rx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{pull_live, pull_ordered, ErrorOutcome, ErrorPolicy, FrameCount};
+    use crate::{
+        pipeline_processing::{
+            node::{Caps, DroppedError, InputProcessingNode, NodeID, ProcessingNode, Request},
+            payload::{Payload, PayloadType},
+            processing_context::ProcessingContext,
+        },
+        util::async_notifier::AsyncNotifier,
+    };
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    // a source that has to be pulled in order, like a TcpReader
+    struct SequentialSource {
+        frames: u64,
+        next: AsyncNotifier<u64>,
+        pulled: Mutex<Vec<(u64, bool)>>,
+    }
+
+    #[async_trait]
+    impl ProcessingNode for SequentialSource {
+        async fn pull(&self, request: Request) -> Result<Payload> {
+            let frame_number = request.frame_number();
+            self.next.wait(move |next| *next == frame_number).await;
+            self.pulled.lock().unwrap().push((frame_number, request.is_droppable()));
+            self.next.update(|next| *next = frame_number + 1);
+
+            if request.is_droppable() {
+                Err(DroppedError.into())
+            } else {
+                Ok(Payload::from(frame_number))
+            }
+        }
+        fn get_caps(&self) -> Caps { Caps { frame_count: Some(self.frames), random_access: false } }
+        fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::Unknown) }
+    }
+
+    #[test]
+    fn test_live_pull_drops_late_frames() {
+        let context = ProcessingContext::default();
+        // the channel holds as many frames as there are threads
+        let capacity = context.num_threads() as u64;
+        let source = Arc::new(SequentialSource {
+            frames: capacity * 4,
+            next: Default::default(),
+            pulled: Default::default(),
+        });
+        let rx = pull_live(
+            &context,
+            0,
+            Arc::new(|_| {}),
+            InputProcessingNode::new(NodeID::from(1), source.clone()),
+            FrameCount::All,
+            ErrorPolicy::Skip,
+        );
+
+        // nothing is received until the source was read to the end
+        context.block_on(source.next.wait(move |next| *next == capacity * 4));
+        let received = rx.iter().map(|payload| *payload.downcast::<u64>().unwrap());
+        assert_eq!(received.collect::<Vec<_>>(), (0..capacity).collect::<Vec<_>>());
+
+        // every frame was pulled exactly once and in order, dropped or not
+        let pulled = source.pulled.lock().unwrap();
+        assert_eq!(
+            pulled.iter().map(|(frame, _)| *frame).collect::<Vec<_>>(),
+            (0..capacity * 4).collect::<Vec<_>>()
+        );
+        // the first frames were requested before anything was delivered, the
+        // channel was full for the ones that were requested after the first
+        // frames were delivered
+        assert!(pulled[..capacity as usize].iter().all(|(_, droppable)| !droppable));
+        assert!(pulled[2 * capacity as usize..].iter().all(|(_, droppable)| *droppable));
+    }
+
+    // a source with random access that always fails on frame 3
+    struct FlakySource;
+
+    #[async_trait]
+    impl ProcessingNode for FlakySource {
+        async fn pull(&self, request: Request) -> Result<Payload> {
+            match request.frame_number() {
+                3 => Err(anyhow::anyhow!("broken frame")),
+                frame_number => Ok(Payload::from(frame_number)),
+            }
+        }
+        fn get_caps(&self) -> Caps { Caps { frame_count: Some(6), random_access: true } }
+        fn get_output_type(&self) -> Result<PayloadType> { Ok(PayloadType::Unknown) }
+    }
+
+    #[test]
+    fn test_error_policies() {
+        assert_eq!("retry:3".parse::<ErrorPolicy>().unwrap(), ErrorPolicy::Retry(3));
+        assert!("retry".parse::<ErrorPolicy>().is_err());
+
+        let context = ProcessingContext::default();
+        let input = InputProcessingNode::new(NodeID::from(1), Arc::new(FlakySource));
+        let rx = pull_ordered(
+            &context,
+            0,
+            Arc::new(|_| {}),
+            input,
+            FrameCount::All,
+            ErrorPolicy::RepeatLast,
+        );
+        let received =
+            rx.iter().map(|payload| *payload.downcast::<u64>().unwrap()).collect::<Vec<_>>();
+        assert_eq!(received, vec![0, 1, 2, 2, 4, 5]);
+
+        let records = context.errors().records();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].frame, records[0].outcome), (3, ErrorOutcome::Repeated));
+        assert!(context.errors().failure().is_none());
+    }
+
+    #[test]
+    fn test_exact_frame_count() {
+        let context = ProcessingContext::default();
+        let pull = |frame_count| {
+            let input = InputProcessingNode::new(NodeID::from(1), Arc::new(FlakySource));
+            let rx =
+                pull_ordered(&context, 0, Arc::new(|_| {}), input, frame_count, ErrorPolicy::Skip);
+            rx.iter().map(|payload| *payload.downcast::<u64>().unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(pull(FrameCount::Indices(5)), vec![0, 1, 2, 4]);
+        assert_eq!(
+            pull(FrameCount::Exact { frames: 5, max_consecutive_failures: 1 }),
+            vec![0, 1, 2, 4, 5]
+        );
+        assert!(context.errors().failure().is_none());
+
+        assert_eq!(
+            pull(FrameCount::Exact { frames: 5, max_consecutive_failures: 0 }),
+            vec![0, 1, 2]
+        );
+        assert_eq!(context.errors().failure().map(|failure| failure.frame), Some(3));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::pipeline_processing::parametrizable::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+fn sorted(
+    nodes: &HashMap<String, ParameterizableDescriptor>,
+) -> Vec<(&String, &ParameterizableDescriptor)> {
+    let mut nodes = nodes.iter().collect::<Vec<_>>();
+    nodes.sort_by_key(|(name, _)| name.as_str());
+    nodes
+}
+
+fn value_to_json(value: &ParameterValue) -> Value {
+    match value {
+        FloatRangeValue(v) => json!(v),
+        IntRangeValue(v) => json!(v),
+        StringValue(v) => json!(v),
+        BoolValue(v) => json!(v),
+        ListValue(v) => Value::Array(v.iter().map(value_to_json).collect()),
+        NodeInputValue(_) => Value::Null,
+    }
+}
+
+fn type_to_json(ty: &ParameterType) -> Value {
+    match ty {
+        FloatRange(min, max) => json!({ "type": "float", "min": min, "max": max }),
+        IntRange(min, max) => json!({ "type": "int", "min": min, "max": max }),
+        ListParameter(ty) => json!({ "type": "list", "items": type_to_json(ty) }),
+        StringParameter => json!({ "type": "string" }),
+        Choice(choices) => json!({ "type": "choice", "values": choices }),
+        BoolParameter => json!({ "type": "bool" }),
+        NodeInputParameter => json!({ "type": "node-input" }),
+    }
+}
+
+/// Describes all nodes with their parameters as JSON, for front-ends that
+/// generate forms or documentation.
+pub fn node_catalogue(nodes: &HashMap<String, ParameterizableDescriptor>) -> Value {
+    let nodes = sorted(nodes)
+        .into_iter()
+        .map(|(name, descriptor)| {
+            let parameters_descriptor = &descriptor.parameters_descriptor;
+            let mut parameters = parameters_descriptor.0.iter().collect::<Vec<_>>();
+            parameters.sort_by_key(|(key, _)| key.as_str());
+            let parameters = parameters
+                .into_iter()
+                .map(|(key, parameter)| {
+                    let mut description = type_to_json(parameter.get_parameter_type());
+                    let object = description.as_object_mut().unwrap();
+                    let (requirement, default) = match parameter {
+                        Mandatory(_) => ("mandatory", None),
+                        Optional(_) => ("optional", None),
+                        WithDefault(_, default) => ("default", Some(value_to_json(default))),
+                    };
+                    object.insert("requirement".to_string(), json!(requirement));
+                    if let Some(default) = default {
+                        object.insert("default".to_string(), default);
+                    }
+                    object.insert("live".to_string(), json!(parameters_descriptor.is_live(key)));
+                    (key.clone(), description)
+                })
+                .collect::<Map<_, _>>();
+            json!({
+                "name": name,
+                "description": descriptor.description,
+                "parameters": parameters,
+                "outputs": descriptor.outputs,
+            })
+        })
+        .collect();
+    Value::Array(nodes)
+}
+
+fn type_to_schema(ty: &ParameterType) -> Value {
+    match ty {
+        FloatRange(min, max) => json!({ "type": "number", "minimum": min, "maximum": max }),
+        IntRange(min, max) => json!({ "type": "integer", "minimum": min, "maximum": max }),
+        ListParameter(ty) => json!({ "type": "array", "items": type_to_schema(ty) }),
+        StringParameter => json!({ "type": "string" }),
+        // the nodes also accept other casings, but editors should suggest the
+        // canonical one
+        Choice(choices) => json!({ "enum": choices }),
+        BoolParameter => json!({ "type": "boolean" }),
+        NodeInputParameter => json!({
+            "type": "string",
+            "pattern": "^<",
+            "description": "another node, written as `<name` or `<name.<output>`",
+        }),
+    }
+}
+
+/// A JSON Schema for pipeline config files. Every entry of a config is
+/// checked against the parameters of its node type. Types that are not
+/// known (like sub-graphs) are accepted with any parameters.
+pub fn config_schema(nodes: &HashMap<String, ParameterizableDescriptor>) -> Value {
+    let nodes = sorted(nodes);
+    let names = nodes.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+
+    let mut definitions = Map::new();
+    let mut dispatch = vec![];
+    for (name, descriptor) in &nodes {
+        let mut properties = Map::new();
+        properties.insert("type".to_string(), json!({ "const": name }));
+        let mut required = vec!["type".to_string()];
+        for (key, parameter) in &descriptor.parameters_descriptor.0 {
+            let mut schema = type_to_schema(parameter.get_parameter_type());
+            match parameter {
+                Mandatory(_) => required.push(key.clone()),
+                Optional(_) => {}
+                WithDefault(_, default) => {
+                    schema
+                        .as_object_mut()
+                        .unwrap()
+                        .insert("default".to_string(), value_to_json(default));
+                }
+            }
+            properties.insert(key.clone(), schema);
+        }
+        required.sort();
+
+        let mut definition = json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        });
+        if let Some(description) = &descriptor.description {
+            definition
+                .as_object_mut()
+                .unwrap()
+                .insert("description".to_string(), json!(description));
+        }
+        definitions.insert(name.to_string(), definition);
+        dispatch.push(json!({
+            "if": { "properties": { "type": { "const": name } } },
+            "then": { "$ref": format!("#/definitions/{name}") },
+        }));
+    }
+    definitions.insert(
+        "node".to_string(),
+        json!({
+            "type": "object",
+            "required": ["type"],
+            "properties": {
+                "type": { "anyOf": [{ "enum": names }, { "type": "string" }] },
+            },
+            "allOf": dispatch,
+        }),
+    );
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "recorder pipeline config",
+        "type": "object",
+        "properties": {
+            "subgraphs": {
+                "description": "sub-graph files that can be used as node types",
+                "type": "array",
+                "items": { "type": "string" },
+            },
+        },
+        "additionalProperties": { "$ref": "#/definitions/node" },
+        "definitions": definitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config_schema, node_catalogue};
+    use crate::nodes::list_available_nodes;
+
+    #[test]
+    fn test_catalogue_and_schema() {
+        let nodes = list_available_nodes();
+
+        let catalogue = node_catalogue(&nodes);
+        let null_source =
+            catalogue.as_array().unwrap().iter().find(|n| n["name"] == "NullFrameSource").unwrap();
+        assert_eq!(null_source["parameters"]["width"]["requirement"], "mandatory");
+        assert_eq!(null_source["parameters"]["fps"]["default"], 24.0);
+
+        let color_voodoo =
+            catalogue.as_array().unwrap().iter().find(|n| n["name"] == "ColorVoodoo").unwrap();
+        assert_eq!(color_voodoo["parameters"]["pedestal"]["live"], true);
+        assert_eq!(color_voodoo["parameters"]["input"]["type"], "node-input");
+        assert_eq!(null_source["parameters"]["bayer"]["type"], "choice");
+        assert_eq!(null_source["parameters"]["bayer"]["values"][3], "GRGB");
+        let histogram =
+            catalogue.as_array().unwrap().iter().find(|n| n["name"] == "Histogram").unwrap();
+        assert_eq!(histogram["outputs"], serde_json::json!(["histogram", "image"]));
+
+        let schema = config_schema(&nodes);
+        let definition = &schema["definitions"]["NullFrameSource"];
+        assert!(definition["required"].as_array().unwrap().contains(&"width".into()));
+        assert_eq!(definition["properties"]["uint-bits"]["maximum"], 16);
+        assert_eq!(schema["additionalProperties"]["$ref"], "#/definitions/node");
+    }
+}
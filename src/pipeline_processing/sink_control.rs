@@ -0,0 +1,186 @@
+use crate::pipeline_processing::{node::ProgressUpdate, processing_context::ProcessingContext};
+use anyhow::{bail, Result};
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+use tokio::sync::Notify;
+
+// the number of frames the fps are averaged over
+const FPS_WINDOW: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SinkState {
+    /// waiting to be armed
+    Disarmed,
+    Running,
+    /// was disarmed and is finishing the frames in flight
+    Stopping,
+    /// the input ended or the pipeline was stopped
+    Finished,
+    Failed,
+}
+
+impl Display for SinkState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SinkState::Disarmed => "disarmed",
+            SinkState::Running => "running",
+            SinkState::Stopping => "stopping",
+            SinkState::Finished => "finished",
+            SinkState::Failed => "failed",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SinkProgress {
+    pub latest_frame: u64,
+    pub total_frames: Option<u64>,
+    /// the number of frames written since the sink was last armed
+    pub frames: u64,
+    /// averaged over the last few frames, `None` until two frames were written
+    pub fps: Option<f64>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    progress: SinkProgress,
+    recent: VecDeque<Instant>,
+}
+
+/// Arms and disarms a sink of a running `ProcessingGraph`. A disarmed sink
+/// finishes the frames in flight, is finalized and waits until it is armed
+/// again. Arming it again resumes after the last frame it pulled, so sources
+/// that have to be read in order continue where they are. Writers that open
+/// their output once (like `RawBlobWriter`) append to it, the ones that open
+/// it in every run (like `FfmpegWriter`) replace it. Sinks start out armed.
+pub struct SinkControl {
+    armed: AtomicBool,
+    notify: Notify,
+    state: Mutex<SinkState>,
+    // the context of the current run, cancelling it disarms the sink
+    context: Mutex<Option<ProcessingContext>>,
+    progress: Mutex<ProgressState>,
+    // the frame after the last one pulled in any run
+    next_frame: AtomicU64,
+    // why the sink can't be disarmed
+    fixed: Option<String>,
+}
+
+impl Default for SinkControl {
+    fn default() -> Self {
+        Self {
+            armed: AtomicBool::new(true),
+            notify: Notify::new(),
+            state: Mutex::new(SinkState::Disarmed),
+            context: Mutex::new(None),
+            progress: Default::default(),
+            next_frame: AtomicU64::new(0),
+            fixed: None,
+        }
+    }
+}
+
+impl SinkControl {
+    /// A control for a sink that always stays armed, `disarm` fails with
+    /// `reason`.
+    pub(crate) fn fixed(reason: String) -> Self { Self { fixed: Some(reason), ..Self::default() } }
+
+    pub fn arm(&self) {
+        self.armed.store(true, Ordering::Relaxed);
+        self.notify.notify_waiters();
+    }
+
+    pub fn disarm(&self) -> Result<()> {
+        if let Some(reason) = &self.fixed {
+            bail!("the sink can't be disarmed: {reason}");
+        }
+        self.armed.store(false, Ordering::Relaxed);
+        if let Some(context) = &*self.context.lock().unwrap() {
+            *self.state.lock().unwrap() = SinkState::Stopping;
+            context.cancel();
+        }
+        Ok(())
+    }
+
+    pub fn is_armed(&self) -> bool { self.armed.load(Ordering::Relaxed) }
+
+    pub fn state(&self) -> SinkState { *self.state.lock().unwrap() }
+
+    pub fn progress(&self) -> SinkProgress { self.progress.lock().unwrap().progress }
+
+    /// Resolves once the sink is armed or `context` was cancelled.
+    pub(crate) async fn armed(&self, context: &ProcessingContext) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_armed() || context.is_cancelled() {
+                return;
+            }
+            tokio::select! {
+                _ = notified => {},
+                _ = context.cancelled() => {},
+            }
+        }
+    }
+
+    /// Creates the context for the next run of the sink.
+    pub(crate) fn start(&self, context: &ProcessingContext) -> ProcessingContext {
+        let context = context.child().starting_at(self.next_frame.load(Ordering::Relaxed));
+        *self.context.lock().unwrap() = Some(context.clone());
+        *self.state.lock().unwrap() = SinkState::Running;
+        *self.progress.lock().unwrap() = Default::default();
+        // disarmed between `armed` and here
+        if !self.is_armed() {
+            context.cancel();
+        }
+        context
+    }
+
+    /// Returns whether the sink should wait to be armed again. That is the case
+    /// if the run was stopped by disarming the sink, also if it was armed
+    /// again in the meantime.
+    pub(crate) fn stop(
+        &self,
+        failed: bool,
+        run_context: &ProcessingContext,
+        graph_context: &ProcessingContext,
+    ) -> bool {
+        *self.context.lock().unwrap() = None;
+        let restart = !failed && run_context.is_cancelled() && !graph_context.is_cancelled();
+        *self.state.lock().unwrap() = match (failed, restart) {
+            (true, _) => SinkState::Failed,
+            (false, true) => SinkState::Disarmed,
+            (false, false) => SinkState::Finished,
+        };
+        restart
+    }
+
+    pub(crate) fn record_progress(&self, update: ProgressUpdate) {
+        self.next_frame.fetch_max(update.latest_frame + 1, Ordering::Relaxed);
+        let mut state = self.progress.lock().unwrap();
+        let now = Instant::now();
+        if state.recent.len() == FPS_WINDOW {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(now);
+
+        let fps = match (state.recent.front(), state.recent.len()) {
+            (Some(first), n) if n > 1 && now > *first => {
+                Some((n - 1) as f64 / now.duration_since(*first).as_secs_f64())
+            }
+            _ => None,
+        };
+        state.progress = SinkProgress {
+            latest_frame: update.latest_frame,
+            total_frames: update.total_frames,
+            frames: state.progress.frames + 1,
+            fps,
+        };
+    }
+}
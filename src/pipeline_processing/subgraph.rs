@@ -0,0 +1,287 @@
+use crate::pipeline_processing::{parametrizable::Parameters, processing_graph::SerdeNodeConfig};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// how deep sub-graphs may include other sub-graphs, guards against include
+// cycles
+const MAX_NESTING: usize = 16;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct SubgraphHeader {
+    name: String,
+    #[serde(default)]
+    inputs: Vec<String>,
+    outputs: HashMap<String, String>,
+    #[serde(default)]
+    parameters: BTreeMap<String, Option<serde_yaml::Value>>,
+    #[serde(default)]
+    subgraphs: Vec<PathBuf>,
+}
+
+/// A named part of a pipeline that can be used like a node type in other
+/// configs. It is defined by a yaml file with two documents, a header
+/// declaring the interface and the nodes:
+///
+/// ```yaml
+/// name: CalibratedRaw
+/// inputs: [input]
+/// outputs:
+///   output: debayer
+///   raw: calibrated
+/// parameters:
+///   darkframe: ~       # mandatory
+///   width: 4096        # with a default
+/// subgraphs: []        # other sub-graph files used by the nodes
+/// ---
+/// calibrated:
+///   type: Calibrate
+///   input: <input
+///   darkframe: {{ darkframe }}
+///   width: {{ width }}
+/// ...
+/// ```
+///
+/// The nodes are a handlebars template, the parameters of the node using
+/// the sub-graph, the variables of the outer config and the defaults are
+/// available in it (in that order of precedence). `<name` refers to the
+/// output `output` (or the only output), `<name.raw` to the output `raw`.
+#[derive(Debug)]
+pub struct Subgraph {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub outputs: HashMap<String, String>,
+    /// the declared parameters with their default, `None` for mandatory
+    /// parameters
+    pub parameters: BTreeMap<String, Option<String>>,
+    /// the sub-graphs the nodes of this sub-graph can use, by name
+    pub subgraphs: HashMap<String, Arc<Subgraph>>,
+    nodes_template: String,
+}
+
+impl Subgraph {
+    /// Loads a sub-graph definition and the sub-graphs it uses. Paths are
+    /// relative to the directory of the file.
+    pub fn load(path: &Path) -> Result<Self> { Self::load_nested(path, 0) }
+
+    fn load_nested(path: &Path, depth: usize) -> Result<Self> {
+        if depth > MAX_NESTING {
+            bail!("sub-graphs are nested more than {MAX_NESTING} levels deep, is there a cycle?");
+        }
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("couldn't read sub-graph {}", path.display()))?;
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::parse(&source, |subgraph| Self::load_nested(&directory.join(subgraph), depth + 1))
+            .with_context(|| format!("invalid sub-graph {}", path.display()))
+    }
+
+    /// Parses a sub-graph definition, `load` is called for every sub-graph
+    /// file it uses.
+    pub fn parse(source: &str, mut load: impl FnMut(&Path) -> Result<Self>) -> Result<Self> {
+        let (header, nodes_template) = split_documents(source).ok_or_else(|| {
+            anyhow!("expected a header and the nodes, separated by a line containing `---`")
+        })?;
+        let header: SubgraphHeader = serde_yaml::from_str(header)?;
+
+        if header.outputs.is_empty() {
+            bail!("a sub-graph needs at least one output");
+        }
+        let parameters = header
+            .parameters
+            .into_iter()
+            .map(|(name, default)| {
+                let default = match default {
+                    None | Some(serde_yaml::Value::Null) => None,
+                    Some(serde_yaml::Value::String(s)) => Some(s),
+                    Some(serde_yaml::Value::Number(n)) => Some(n.to_string()),
+                    Some(serde_yaml::Value::Bool(b)) => Some(b.to_string()),
+                    Some(other) => {
+                        bail!("the default of parameter {name} has to be a scalar, got {other:?}")
+                    }
+                };
+                Ok((name, default))
+            })
+            .collect::<Result<_>>()?;
+        let subgraphs = header
+            .subgraphs
+            .iter()
+            .map(|path| load(path).map(|subgraph| (subgraph.name.clone(), Arc::new(subgraph))))
+            .collect::<Result<_>>()?;
+
+        Ok(Subgraph {
+            name: header.name,
+            inputs: header.inputs,
+            outputs: header.outputs,
+            parameters,
+            subgraphs,
+            nodes_template: nodes_template.to_string(),
+        })
+    }
+
+    /// The output `<name` refers to.
+    pub fn default_output(&self) -> Option<&str> {
+        match self.outputs.get("output") {
+            Some(_) => Some("output"),
+            None if self.outputs.len() == 1 => self.outputs.keys().next().map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// Renders the nodes of an instance of this sub-graph. Returns the nodes
+    /// and the variables, that nested sub-graphs inherit.
+    pub(crate) fn render(
+        &self,
+        parameters: &Parameters,
+        vars: &BTreeMap<String, String>,
+    ) -> Result<(HashMap<String, SerdeNodeConfig>, BTreeMap<String, String>)> {
+        let mut vars = vars.clone();
+        for (name, default) in &self.parameters {
+            match parameters.iter().find(|(key, _)| *key == name) {
+                Some((_, value)) => {
+                    vars.insert(name.clone(), value.to_string());
+                }
+                None => match (vars.contains_key(name), default) {
+                    (true, _) => {}
+                    (false, Some(default)) => {
+                        vars.insert(name.clone(), default.clone());
+                    }
+                    (false, None) => bail!("sub-graph {} needs the parameter {name}", self.name),
+                },
+            }
+        }
+        if let Some((key, _)) =
+            parameters.iter().find(|(key, _)| !self.parameters.contains_key(*key))
+        {
+            bail!("sub-graph {} has no parameter {key}", self.name);
+        }
+
+        let mut handlebars = handlebars::Handlebars::new();
+        handlebars.set_strict_mode(true);
+        let nodes = serde_yaml::from_str(&handlebars.render_template(&self.nodes_template, &vars)?)
+            .with_context(|| format!("couldn't parse the nodes of sub-graph {}", self.name))?;
+        Ok((nodes, vars))
+    }
+}
+
+fn split_documents(source: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            if offset == 0 {
+                // a leading document marker
+                offset += line.len();
+                continue;
+            }
+            return Some((&source[..offset], &source[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Subgraph;
+    use crate::pipeline_processing::{
+        processing_context::ProcessingContext,
+        processing_graph::{
+            GraphFormat,
+            ProcessingGraphBuilder,
+            ProcessingNodeConfig,
+            SerdeNodeConfig,
+        },
+    };
+    use anyhow::{anyhow, Result};
+    use indoc::indoc;
+    use std::collections::BTreeMap;
+
+    fn parse(source: &str) -> Result<Subgraph> {
+        Subgraph::parse(source, |path| Err(anyhow!("unexpected include of {path:?}")))
+    }
+
+    fn node(yaml: &str) -> ProcessingNodeConfig<String> {
+        serde_yaml::from_str::<SerdeNodeConfig>(yaml).unwrap().into()
+    }
+
+    #[test]
+    fn test_subgraph_expansion() {
+        let subgraph = parse(indoc!(
+            "
+            name: ConvertedCache
+            inputs: [input]
+            outputs:
+              output: cache
+              converted: convert
+            ---
+            convert:
+              type: BitDepthConverter
+              input: <input
+            cache:
+              type: Cache
+              input: <convert
+            "
+        ))
+        .unwrap();
+
+        let mut builder = ProcessingGraphBuilder::new();
+        let source = "{type: NullFrameSource, width: 64, height: 64, uint-bits: 12}";
+        builder.add("source".to_string(), node(source)).unwrap();
+        builder.add("sink".to_string(), node("{type: BenchmarkSink, input: <chain}")).unwrap();
+        let sink = "{type: BenchmarkSink, input: <chain.converted}";
+        builder.add("converted_sink".to_string(), node(sink)).unwrap();
+        let chain = node("{type: ConvertedCache, input: <source}");
+        builder.add_subgraph("chain".to_string(), &subgraph, chain, &BTreeMap::new()).unwrap();
+
+        let mut names = builder.node_ids().keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["chain/cache", "chain/convert", "converted_sink", "sink", "source"]);
+        let dot = builder.render(GraphFormat::Dot).unwrap();
+        assert!(dot.contains("\\\"chain/convert\\\": BitDepthConverter"), "{dot}");
+
+        let graph = builder.build(&ProcessingContext::default()).unwrap();
+        assert!(graph.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_subgraph_parameters() {
+        let subgraph = parse(indoc!(
+            "
+            name: Source
+            outputs:
+              frames: source
+            parameters:
+              width: ~
+              height: 64
+            ---
+            source:
+              type: NullFrameSource
+              width: {{ width }}
+              height: {{ height }}
+              uint-bits: 8
+            "
+        ))
+        .unwrap();
+        assert_eq!(subgraph.default_output(), Some("frames"));
+
+        let render = |config: &str, vars: &[(&str, &str)]| {
+            let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            let (nodes, _) = subgraph.render(&node(config).parameters, &vars)?;
+            Ok::<_, anyhow::Error>(format!("{:?}", nodes["source"]))
+        };
+        let error = |config: &str| render(config, &[]).unwrap_err().to_string();
+        assert!(error("{type: Source}").contains("needs the parameter width"));
+        assert!(error("{type: Source, width: 8, heigth: 8}").contains("no parameter heigth"));
+
+        // parameters of the node take precedence over variables of the outer
+        // config, which take precedence over the defaults
+        let rendered = render("{type: Source, width: 8}", &[("height", "32")]).unwrap();
+        assert!(rendered.contains("Int(32)"), "{rendered}");
+        let rendered = render("{type: Source, width: 8, height: 16}", &[("height", "32")]).unwrap();
+        assert!(rendered.contains("Int(16)"), "{rendered}");
+    }
+}
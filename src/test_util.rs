@@ -0,0 +1,378 @@
+//! Helpers for testing nodes on the cpu without building a graph: a source
+//! that plays back given frames, a sink that collects what it pulled and
+//! comparisons against golden files.
+
+use crate::pipeline_processing::{
+    buffers::CpuBuffer,
+    frame::{
+        CfaDescriptor,
+        ColorInterpretation,
+        Compression,
+        Frame,
+        FrameInterpretation,
+        FrameMetadata,
+        SampleInterpretation,
+    },
+    node::{
+        Caps,
+        DroppedError,
+        EOFError,
+        InputProcessingNode,
+        NodeID,
+        ProcessingNode,
+        ProgressUpdate,
+        Request,
+        SinkNode,
+    },
+    parametrizable::prelude::*,
+    payload::{Payload, PayloadType},
+    processing_context::ProcessingContext,
+    processing_graph::{ProcessingNodeConfig, SerdeNodeConfig},
+    puller::{pull_ordered, ErrorPolicy, FrameCount},
+};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Creates a frame in cpu memory with the given contents.
+pub fn frame(
+    context: &ProcessingContext,
+    interpretation: FrameInterpretation,
+    bytes: &[u8],
+) -> Frame<CpuBuffer> {
+    assert_eq!(bytes.len(), interpretation.required_bytes(), "wrong size for {interpretation}");
+    let mut storage = unsafe { context.get_uninit_cpu_buffer(bytes.len()) };
+    storage.as_mut_slice(|storage| storage.copy_from_slice(bytes));
+    Frame { storage, interpretation, metadata: FrameMetadata::new() }
+}
+
+/// One frame of a single 8 bit rgb pixel per value, all samples of a frame are
+/// its value. Makes it easy to tell which frames a node passed on, see
+/// `counter_value`.
+pub fn counter_frames(
+    context: &ProcessingContext,
+    values: impl IntoIterator<Item = u8>,
+) -> Vec<Frame<CpuBuffer>> {
+    let interpretation = FrameInterpretation {
+        width: 1,
+        height: 1,
+        fps: None,
+        color_interpretation: ColorInterpretation::Rgb,
+        sample_interpretation: SampleInterpretation::UInt(8),
+        compression: Compression::Uncompressed,
+    };
+    values.into_iter().map(|value| frame(context, interpretation, &[value; 3])).collect()
+}
+
+/// The value of a frame created by `counter_frames`.
+pub fn counter_value(frame: &Frame<CpuBuffer>) -> u8 { frame.storage.as_slice(|slice| slice[0]) }
+
+/// `count` 12 bit bayer frames (GBGR) of 8x4 pixels with varying samples, for
+/// round trips through writers and readers.
+pub fn bayer_frames(context: &ProcessingContext, count: u64, fps: f64) -> Vec<Frame<CpuBuffer>> {
+    let interpretation = FrameInterpretation {
+        width: 8,
+        height: 4,
+        fps: Some(fps),
+        color_interpretation: ColorInterpretation::Bayer(CfaDescriptor {
+            red_in_first_col: false,
+            red_in_first_row: true,
+        }),
+        sample_interpretation: SampleInterpretation::UInt(12),
+        compression: Compression::Uncompressed,
+    };
+    (0..count)
+        .map(|n| {
+            let samples = (0..32).map(|i| (n * 32 + i) * 127 % 4096).collect::<Vec<_>>();
+            frame(context, interpretation, &pack_samples(&samples, 12))
+        })
+        .collect()
+}
+
+/// Packs unsigned integer samples most significant bit first, the way the
+/// nodes expect `UInt(bits)` frames.
+pub fn pack_samples(samples: &[u64], bits: u8) -> Vec<u8> {
+    let mut packed = Vec::with_capacity((samples.len() * bits as usize + 7) / 8);
+    let (mut accumulator, mut accumulated_bits) = (0u64, 0);
+    for sample in samples {
+        assert!(*sample < 1 << bits, "{sample} doesn't fit into {bits} bits");
+        accumulator = (accumulator << bits) | sample;
+        accumulated_bits += bits;
+        while accumulated_bits >= 8 {
+            accumulated_bits -= 8;
+            packed.push((accumulator >> accumulated_bits) as u8);
+        }
+        accumulator &= (1 << accumulated_bits) - 1;
+    }
+    if accumulated_bits > 0 {
+        packed.push((accumulator << (8 - accumulated_bits)) as u8);
+    }
+    packed
+}
+
+/// The samples of a frame buffer with the given interpretation.
+pub fn unpack_samples(bytes: &[u8], interpretation: SampleInterpretation) -> Vec<f64> {
+    match interpretation {
+        SampleInterpretation::UInt(bits) => {
+            let bits = bits as usize;
+            (0..bytes.len() * 8 / bits)
+                .map(|i| {
+                    (i * bits..(i + 1) * bits).fold(0u64, |sample, bit| {
+                        (sample << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as u64
+                    }) as f64
+                })
+                .collect()
+        }
+        SampleInterpretation::FP16 => bytes
+            .chunks_exact(2)
+            .map(|half| f16_to_f64(u16::from_ne_bytes([half[0], half[1]])))
+            .collect(),
+        SampleInterpretation::FP32 => bytes
+            .chunks_exact(4)
+            .map(|float| f32::from_ne_bytes([float[0], float[1], float[2], float[3]]) as f64)
+            .collect(),
+    }
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f64;
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+/// Plays back a list of frames, frame `n` of the output is the `n`-th frame.
+pub struct MockSource {
+    frames: Vec<Arc<Frame<CpuBuffer>>>,
+    requests: Mutex<Vec<u64>>,
+}
+
+impl MockSource {
+    pub fn new(frames: Vec<Frame<CpuBuffer>>) -> Arc<Self> {
+        Arc::new(Self {
+            frames: frames.into_iter().map(Arc::new).collect(),
+            requests: Mutex::new(vec![]),
+        })
+    }
+
+    /// The frame numbers that were requested so far, in the order of the
+    /// requests.
+    pub fn requests(&self) -> Vec<u64> { self.requests.lock().clone() }
+}
+
+#[async_trait]
+impl ProcessingNode for MockSource {
+    async fn pull(&self, request: Request) -> Result<Payload> {
+        self.requests.lock().push(request.frame_number());
+        let frame = self.frames.get(request.frame_number() as usize).ok_or(EOFError)?;
+        if request.is_droppable() {
+            return Err(DroppedError.into());
+        }
+        Ok(Payload::from_arc(frame.clone()))
+    }
+
+    fn get_caps(&self) -> Caps {
+        Caps { frame_count: Some(self.frames.len() as u64), random_access: true }
+    }
+
+    fn get_output_type(&self) -> Result<PayloadType> {
+        let first = self.frames.first().map(|frame| frame.interpretation);
+        if self.frames.iter().all(|frame| Some(frame.interpretation) == first) {
+            Ok(PayloadType::Frame(first))
+        } else {
+            Ok(PayloadType::Frame(None))
+        }
+    }
+}
+
+/// Wraps a node so that it can be used as the input of another node.
+pub fn input(node: Arc<dyn ProcessingNode + Send + Sync>) -> InputProcessingNode {
+    InputProcessingNode::new(NodeID::default(), node)
+}
+
+/// Creates a node from its parameters written as yaml (like in a config file)
+/// and the given inputs.
+pub fn node<T: Parameterizable>(
+    context: &ProcessingContext,
+    yaml: &str,
+    inputs: Vec<(&str, InputProcessingNode)>,
+) -> Result<T> {
+    let config: ProcessingNodeConfig<String> =
+        serde_yaml::from_str::<SerdeNodeConfig>(yaml)?.into();
+    let inputs = inputs.into_iter().map(|(name, input)| (name.to_string(), input)).collect();
+    let parameters = config.parameters.add_inputs(inputs).add_defaults(T::describe_parameters())?;
+    T::from_parameters(parameters, &[], context)
+}
+
+/// Pulls the frames of its input in order, with the `fail` error policy.
+pub struct CollectingSink {
+    input: InputProcessingNode,
+    frame_count: FrameCount,
+    frames: Mutex<Vec<Arc<Frame<CpuBuffer>>>>,
+}
+
+impl CollectingSink {
+    pub fn new(input: InputProcessingNode, frame_count: FrameCount) -> Self {
+        Self { input, frame_count, frames: Mutex::new(vec![]) }
+    }
+
+    pub fn frames(&self) -> Vec<Arc<Frame<CpuBuffer>>> { self.frames.lock().clone() }
+}
+
+#[async_trait]
+impl SinkNode for CollectingSink {
+    async fn run(
+        &self,
+        context: &ProcessingContext,
+        progress_callback: Arc<dyn Fn(ProgressUpdate) + Send + Sync>,
+    ) -> Result<()> {
+        let rx = pull_ordered(
+            context,
+            0,
+            progress_callback,
+            self.input.clone_for_same_puller(),
+            self.frame_count,
+            ErrorPolicy::Fail,
+        );
+        while let Ok(payload) = rx.recv_async().await {
+            self.frames.lock().push(context.ensure_cpu_buffer_frame(&payload)?);
+        }
+        Ok(())
+    }
+
+    fn check_input_types(&self) -> Result<()> {
+        self.input.get_output_type()?.expect_frame().map(|_| ())
+    }
+}
+
+/// Runs and finalizes a sink like the graph would. Fails if the puller of the
+/// sink gave up on a frame.
+pub fn run_sink(context: &ProcessingContext, sink: &dyn SinkNode) -> Result<()> {
+    sink.check_input_types()?;
+    context.block_on(async {
+        let result = sink.run(context, Arc::new(|_| {})).await;
+        sink.finalize(context).await?;
+        result
+    })?;
+    match context.errors().failure() {
+        Some(failure) => bail!("frame {} failed: {}", failure.frame, failure.error),
+        None => Ok(()),
+    }
+}
+
+/// Pulls the frames of a node, with `CollectingSink`.
+pub fn collect_frames(
+    context: &ProcessingContext,
+    node: Arc<dyn ProcessingNode + Send + Sync>,
+    frame_count: FrameCount,
+) -> Result<Vec<Arc<Frame<CpuBuffer>>>> {
+    let sink = CollectingSink::new(input(node), frame_count);
+    run_sink(context, &sink)?;
+    Ok(sink.frames())
+}
+
+/// A directory for the files of a test, removed before the test runs.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("recorder-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.raw"))
+}
+
+/// Compares a frame against the golden file `tests/golden/<name>.raw`. Every
+/// sample may differ by at most `tolerance`. Run the tests with
+/// `UPDATE_GOLDEN=1` to write the golden files from the current output.
+pub fn assert_golden(name: &str, frame: &Frame<CpuBuffer>, tolerance: f64) {
+    let path = golden_path(name);
+    let actual = frame.storage.as_slice(|slice| slice.to_vec());
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read(&path)
+        .map_err(|error| anyhow!("{error}, run with UPDATE_GOLDEN=1 to create {path:?}"))
+        .unwrap();
+    if let Err(error) = compare_samples(&frame.interpretation, &expected, &actual, tolerance) {
+        panic!("frame differs from golden file {path:?}: {error}");
+    }
+}
+
+fn compare_samples(
+    interpretation: &FrameInterpretation,
+    expected: &[u8],
+    actual: &[u8],
+    tolerance: f64,
+) -> Result<()> {
+    if expected.len() != actual.len() {
+        bail!("expected {} bytes, got {}", expected.len(), actual.len());
+    }
+    let expected = unpack_samples(expected, interpretation.sample_interpretation);
+    let actual = unpack_samples(actual, interpretation.sample_interpretation);
+    let samples_per_row =
+        interpretation.width * interpretation.color_interpretation.samples_per_pixel();
+    let differences = expected
+        .iter()
+        .zip(&actual)
+        .enumerate()
+        .filter(|(_, (expected, actual))| !((*expected - *actual).abs() <= tolerance))
+        .collect::<Vec<_>>();
+    match differences.first() {
+        None => Ok(()),
+        Some((i, (expected, actual))) => bail!(
+            "{} samples differ by more than {tolerance}, the first is sample {} of row {}: \
+             expected {expected}, got {actual}",
+            differences.len(),
+            *i as u64 % samples_per_row,
+            *i as u64 / samples_per_row,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_samples, pack_samples, unpack_samples};
+    use crate::pipeline_processing::frame::{
+        ColorInterpretation,
+        Compression,
+        FrameInterpretation,
+        SampleInterpretation,
+    };
+
+    #[test]
+    fn test_pack_and_compare() {
+        let samples = [0xabc, 0x123, 0xfff, 0x001];
+        let packed = pack_samples(&samples, 12);
+        assert_eq!(packed, [0xab, 0xc1, 0x23, 0xff, 0xf0, 0x01]);
+        let unpacked = unpack_samples(&packed, SampleInterpretation::UInt(12));
+        assert_eq!(unpacked, samples.map(|s| s as f64));
+
+        let interpretation = FrameInterpretation {
+            width: 2,
+            height: 2,
+            fps: None,
+            color_interpretation: ColorInterpretation::Rgb,
+            sample_interpretation: SampleInterpretation::UInt(8),
+            compression: Compression::Uncompressed,
+        };
+        let expected = [0u8; 12];
+        let mut actual = [0u8; 12];
+        actual[7] = 2;
+        assert!(compare_samples(&interpretation, &expected, &actual, 2.0).is_ok());
+        let error = compare_samples(&interpretation, &expected, &actual, 1.0).unwrap_err();
+        assert!(error.to_string().contains("sample 1 of row 1"), "{error}");
+    }
+}
@@ -0,0 +1,210 @@
+use crate::pipeline_processing::{
+    metrics::payload_bytes,
+    node::{PinCache, Request},
+    payload::Payload,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    hash::Hash,
+};
+
+/// A least recently used cache that holds values up to a total size in bytes.
+///
+/// Entries that were last inserted or looked up with `pinned` set are never
+/// evicted, so the budget can be exceeded while they are pinned. The next
+/// access without `pinned` releases them again.
+pub struct Lru<K, V> {
+    max_bytes: usize,
+    inner: Mutex<Inner<K, V>>,
+}
+
+struct Inner<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    // the keys by the time of their last use, the least recently used first
+    order: BTreeMap<u64, K>,
+    clock: u64,
+    stats: LruStats,
+}
+
+struct Entry<V> {
+    value: V,
+    bytes: usize,
+    last_use: u64,
+    pinned: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LruStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl LruStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl Display for LruStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evictions, {:.1} MiB in {} entries",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions,
+            self.bytes as f64 / (1024.0 * 1024.0),
+            self.entries,
+        )
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Lru<K, V> {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                clock: 0,
+                stats: LruStats::default(),
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &K, pinned: bool) -> Option<V> {
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+        let entry = match inner.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => {
+                inner.stats.misses += 1;
+                return None;
+            }
+        };
+        inner.stats.hits += 1;
+        inner.clock += 1;
+        inner.order.remove(&entry.last_use);
+        inner.order.insert(inner.clock, key.clone());
+        entry.last_use = inner.clock;
+        let was_pinned = std::mem::replace(&mut entry.pinned, pinned);
+        let value = entry.value.clone();
+        if was_pinned && !pinned {
+            inner.evict(self.max_bytes);
+        }
+        Some(value)
+    }
+
+    /// Inserts or replaces the value for `key`, `bytes` is its size that
+    /// counts towards the budget.
+    pub fn insert(&self, key: K, value: V, bytes: usize, pinned: bool) {
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+        inner.clock += 1;
+        let entry = Entry { value, bytes, last_use: inner.clock, pinned };
+        inner.order.insert(inner.clock, key.clone());
+        inner.stats.bytes += bytes;
+        if let Some(old) = inner.entries.insert(key, entry) {
+            inner.order.remove(&old.last_use);
+            inner.stats.bytes -= old.bytes;
+        }
+        inner.evict(self.max_bytes);
+    }
+
+    pub fn stats(&self) -> LruStats {
+        let inner = self.inner.lock();
+        LruStats { entries: inner.entries.len(), ..inner.stats }
+    }
+}
+
+impl<K: Hash + Eq, V> Inner<K, V> {
+    fn evict(&mut self, max_bytes: usize) {
+        let mut bytes = self.stats.bytes;
+        let mut evicted = vec![];
+        for (last_use, key) in &self.order {
+            if bytes <= max_bytes {
+                break;
+            }
+            let entry = &self.entries[key];
+            if !entry.pinned {
+                bytes -= entry.bytes;
+                evicted.push(*last_use);
+            }
+        }
+        for last_use in evicted {
+            let key = self.order.remove(&last_use).unwrap();
+            self.entries.remove(&key);
+            self.stats.evictions += 1;
+        }
+        self.stats.bytes = bytes;
+    }
+}
+
+/// Caches the frames of a node by their frame number.
+pub type FrameLru = Lru<u64, Payload>;
+
+impl FrameLru {
+    /// Looks up a frame, pinning it if the request carries `PinCache`.
+    pub fn get_frame(&self, frame_number: u64, request: &Request) -> Option<Payload> {
+        self.get(&frame_number, request.get_extra::<PinCache>().is_some())
+    }
+
+    pub fn insert_frame(&self, frame_number: u64, payload: Payload, request: &Request) {
+        let bytes = payload_bytes(&payload) as usize;
+        self.insert(frame_number, payload, bytes, request.get_extra::<PinCache>().is_some());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lru;
+
+    #[test]
+    fn test_lru() {
+        let lru = Lru::new(30);
+        lru.insert(1, "a", 10, false);
+        lru.insert(2, "b", 10, false);
+        lru.insert(3, "c", 10, false);
+        // 1 is now used more recently than 2
+        assert_eq!(lru.get(&1, false), Some("a"));
+        lru.insert(4, "d", 20, false);
+        assert_eq!(lru.get(&2, false), None);
+        assert_eq!(lru.get(&3, false), None);
+        assert_eq!(lru.get(&1, false), Some("a"));
+
+        let stats = lru.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 2, 2));
+        assert_eq!((stats.entries, stats.bytes), (2, 30));
+    }
+
+    #[test]
+    fn test_pinned() {
+        let lru = Lru::new(20);
+        lru.insert(1, "a", 10, true);
+        lru.insert(2, "b", 10, false);
+        lru.insert(3, "c", 10, false);
+        assert_eq!(lru.get(&2, false), None);
+        assert_eq!(lru.get(&1, false), Some("a"));
+
+        // the budget is exceeded while all entries are pinned
+        lru.get(&3, true);
+        lru.get(&1, true);
+        lru.insert(4, "d", 10, true);
+        assert_eq!(lru.stats().bytes, 30);
+        // releasing 3 evicts it, the other entries are still pinned
+        lru.get(&3, false);
+        assert_eq!(lru.get(&3, false), None);
+        assert_eq!(lru.stats().bytes, 20);
+    }
+}
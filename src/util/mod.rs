@@ -1,2 +1,3 @@
 pub mod async_notifier;
 pub mod fps_report;
+pub mod lru;
@@ -0,0 +1,268 @@
+use hyper::{Body, Client, Method, Request, StatusCode};
+use indoc::indoc;
+use recorder::{
+    control_server::ControlServer,
+    pipeline_processing::{
+        processing_context::ProcessingContext,
+        processing_graph::{ProcessingGraphBuilder, SerdeNodeConfig},
+    },
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::Write,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+struct TestClient {
+    address: SocketAddr,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl TestClient {
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: &str,
+    ) -> anyhow::Result<(StatusCode, Value)> {
+        self.runtime.block_on(async {
+            let request = Request::builder()
+                .method(method)
+                .uri(format!("http://{}{path}", self.address))
+                .body(Body::from(body.to_string()))?;
+            let response = Client::new().request(request).await?;
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok((status, serde_json::from_slice(&body)?))
+        })
+    }
+
+    fn get(&self, path: &str) -> Value {
+        let (status, value) = self.request(Method::GET, path, "").unwrap();
+        assert_eq!(status, StatusCode::OK, "{value}");
+        value
+    }
+
+    fn post(&self, path: &str) {
+        let (status, value) = self.request(Method::POST, path, "").unwrap();
+        assert_eq!(status, StatusCode::OK, "{value}");
+    }
+
+    fn sink_status(&self, name: &str) -> Value {
+        let status = self.get("/status");
+        status["sinks"].as_array().unwrap().iter().find(|s| s["name"] == name).unwrap().clone()
+    }
+
+    /// Polls the status of the sink `name` until `condition` holds.
+    fn wait_for_sink(&self, name: &str, condition: impl Fn(&Value) -> bool) -> Value {
+        let start = Instant::now();
+        loop {
+            let status = self.sink_status(name);
+            if condition(&status) {
+                return status;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out, last status: {status}");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+struct RunningGraph {
+    client: TestClient,
+    context: ProcessingContext,
+    run: JoinHandle<anyhow::Result<()>>,
+    server: JoinHandle<anyhow::Result<()>>,
+}
+
+impl RunningGraph {
+    /// Builds the graph and runs it with a control server.
+    fn start(yaml: &str) -> Self {
+        let nodes: HashMap<String, SerdeNodeConfig> = serde_yaml::from_str(yaml).unwrap();
+        let mut builder = ProcessingGraphBuilder::new();
+        for (name, node) in nodes {
+            builder.add(name, node.into()).unwrap();
+        }
+        let node_ids = builder.node_ids().clone();
+        let context = ProcessingContext::default();
+        let graph = Arc::new(builder.build(&context).unwrap());
+
+        let address = SocketAddr::from(([127, 0, 0, 1], portpicker::pick_unused_port().unwrap()));
+        let server = ControlServer::new(graph.clone(), node_ids, context.clone());
+        let server = {
+            let context = context.clone();
+            std::thread::spawn(move || context.block_on(server.serve(address)))
+        };
+        let run = {
+            let context = context.clone();
+            std::thread::spawn(move || graph.run(context, |_| {}))
+        };
+
+        let client = TestClient { address, runtime: tokio::runtime::Runtime::new().unwrap() };
+        let start = Instant::now();
+        while client.request(Method::GET, "/status", "").is_err() {
+            assert!(start.elapsed() < Duration::from_secs(10), "control server didn't start");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Self { client, context, run, server }
+    }
+
+    fn shutdown(self) {
+        self.client.post("/shutdown");
+        self.run.join().unwrap().unwrap();
+        self.server.join().unwrap().unwrap();
+        assert!(self.client.request(Method::GET, "/status", "").is_err());
+    }
+}
+
+#[test]
+fn test_control_server() {
+    let graph = RunningGraph::start(indoc!(
+        "
+        source:
+          type: NullFrameSource
+          width: 64
+          height: 64
+          uint-bits: 8
+        cache:
+          type: LruCache
+          input: <source
+        preview:
+          type: BenchmarkSink
+          input: <cache
+        recorder:
+          type: BenchmarkSink
+          input: <cache
+        "
+    ));
+    let client = &graph.client;
+
+    let nodes = client.get("/nodes");
+    let names = nodes.as_array().unwrap().iter().map(|n| n["name"].clone()).collect::<Vec<_>>();
+    assert_eq!(names.len(), 4);
+    let source = nodes.as_array().unwrap().iter().find(|n| n["name"] == "source").unwrap();
+    assert_eq!(source["type"], "NullFrameSource");
+    assert_eq!(source["parameters"]["width"], "64");
+    assert_eq!(source["sink"], false);
+
+    // only live parameters can be changed
+    let (status, value) =
+        client.request(Method::PUT, "/nodes/source/parameters", r#"{"width": 128}"#).unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(value["error"].as_str().unwrap().contains("can't be changed while running"));
+
+    client.wait_for_sink("recorder", |s| s["state"] == "running" && s["frames"].as_u64() > Some(0));
+
+    let status = client.get("/status");
+    let node = |name: &str| {
+        status["nodes"].as_array().unwrap().iter().find(|n| n["name"] == name).unwrap().clone()
+    };
+    assert!(node("cache")["cache"]["misses"].as_u64().unwrap() > 0, "{status}");
+    assert!(node("source")["cache"].is_null(), "{status}");
+
+    // disarming the recorder keeps the preview running
+    client.post("/sinks/recorder/stop");
+    client.wait_for_sink("recorder", |s| s["state"] == "disarmed");
+    let frames = client.sink_status("preview")["frames"].as_u64().unwrap();
+    let preview = client.wait_for_sink("preview", |s| s["frames"].as_u64().unwrap() > frames);
+    assert_eq!(preview["state"], "running");
+    assert!(preview["fps"].as_f64().unwrap() > 0.0);
+
+    client.post("/sinks/recorder/start");
+    client.wait_for_sink("recorder", |s| s["state"] == "running");
+
+    // arming it again before the run stopped still resumes it
+    let stopped = client.wait_for_sink("recorder", |s| s["frames"].as_u64() > Some(0));
+    client.post("/sinks/recorder/stop");
+    client.post("/sinks/recorder/start");
+    let resumed = client
+        .wait_for_sink("recorder", |s| s["state"] == "running" && s["frames"].as_u64() > Some(0));
+    assert!(resumed["latest-frame"].as_u64() > stopped["latest-frame"].as_u64(), "{resumed}");
+
+    let (status, _) = client.request(Method::POST, "/sinks/source/stop", "").unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    graph.shutdown();
+}
+
+/// Serves an endless stream of zero frames of `frame_bytes` bytes to every
+/// connection, like a camera streaming over tcp.
+fn serve_frames(frame_bytes: usize) -> SocketAddr {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            std::thread::spawn(move || {
+                let frame = vec![0u8; frame_bytes];
+                // ends once the reader is gone
+                while stream.write_all(&frame).is_ok() {}
+            });
+        }
+    });
+    address
+}
+
+#[test]
+fn test_sequential_source() {
+    let graph = RunningGraph::start(&format!(
+        indoc!(
+            "
+            shared:
+              type: TcpReader
+              address: {}
+              width: 8
+              height: 8
+              uint-bits: 8
+            cache:
+              type: Cache
+              input: <shared
+            preview:
+              type: BenchmarkSink
+              input: <cache
+            recorder:
+              type: BenchmarkSink
+              input: <cache
+            single:
+              type: TcpReader
+              address: {}
+              width: 8
+              height: 8
+              uint-bits: 8
+            standalone:
+              type: BenchmarkSink
+              input: <single
+            "
+        ),
+        serve_frames(64),
+        serve_frames(64),
+    ));
+    let client = &graph.client;
+
+    // the frames the recorder would skip would fill up the cache and stall
+    // the preview
+    client.wait_for_sink("recorder", |s| s["frames"].as_u64() > Some(0));
+    let (status, value) = client.request(Method::POST, "/sinks/recorder/stop", "").unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(value["error"].as_str().unwrap().contains("\"cache\" (Cache)"), "{value}");
+    assert_eq!(client.sink_status("recorder")["state"], "running");
+
+    // a sink that is armed again continues with the next frame of the stream
+    client.wait_for_sink("standalone", |s| s["frames"].as_u64() > Some(0));
+    client.post("/sinks/standalone/stop");
+    let stopped = client.wait_for_sink("standalone", |s| s["state"] == "disarmed");
+    client.post("/sinks/standalone/start");
+    let resumed = client
+        .wait_for_sink("standalone", |s| s["state"] == "running" && s["frames"].as_u64() > Some(0));
+    assert!(resumed["latest-frame"].as_u64() > stopped["latest-frame"].as_u64(), "{resumed}");
+
+    // the preview is still fed
+    let frames = client.sink_status("preview")["frames"].as_u64().unwrap();
+    client.wait_for_sink("preview", |s| s["frames"].as_u64().unwrap() > frames);
+    assert!(graph.context.errors().records().is_empty());
+
+    graph.shutdown();
+}